@@ -1,4 +1,5 @@
 use chat_formatting::{
+    ansi::AnsiColorMode,
     chat::{Chat, TextFormatter},
     translator::Translator,
 };
@@ -14,6 +15,7 @@ fn main() {
         Default::default()
     };
 
+    let color_mode = AnsiColorMode::detect();
     for line in std::io::stdin().lines() {
         if let Err(err) = line {
             eprintln!("Error parsing a line: {:?}", err);
@@ -26,6 +28,6 @@ fn main() {
         } else {
             Chat::Legacy(line)
         };
-        println!("{}", chat.to_ansi_string(&translator));
+        println!("{}", chat.to_ansi_string_with(&translator, color_mode));
     }
 }
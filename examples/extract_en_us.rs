@@ -0,0 +1,23 @@
+use std::collections::BTreeMap;
+
+use chat_formatting::resource_pack::ResourcePack;
+
+/// Write the en_us translations of a client jar, like the embedded assets/en_us.json
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} <client.jar> <en_us.json>", args[0]);
+        std::process::exit(1);
+    }
+    let pack = ResourcePack::from_zip(&args[1]).expect("Read client jar");
+    let translator = pack
+        .translations
+        .get("en_us")
+        .expect("Client jar contains en_us translations");
+    let translations: BTreeMap<&str, &str> = translator
+        .keys()
+        .filter_map(|key| Some((key, translator.get(key)?)))
+        .collect();
+    let json = serde_json::to_string_pretty(&translations).expect("Serialize translations");
+    std::fs::write(&args[2], json + "\n").expect("Write translations");
+}
@@ -0,0 +1,295 @@
+use crate::{
+    chat::Chat,
+    color,
+    formatting::ChatColor,
+    style::{push_run, runs_to_chat, Style, TextRun, FORMATS},
+};
+
+/// How many colors the terminal supports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AnsiColorMode {
+    /// 24-bit colors
+    #[default]
+    TrueColor,
+    /// The xterm 256 color palette
+    Ansi256,
+    /// The 16 standard colors
+    Ansi16,
+    /// Only formats like bold, but no colors
+    None,
+}
+
+impl AnsiColorMode {
+    /// Detect the supported mode of the current terminal using the environment
+    /// variables NO_COLOR, COLORTERM and TERM
+    pub fn detect() -> Self {
+        Self::detect_with(|name| std::env::var(name).ok())
+    }
+
+    fn detect_with(var: impl Fn(&str) -> Option<String>) -> Self {
+        // See https://no-color.org
+        if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return AnsiColorMode::None;
+        }
+        if let Some("truecolor" | "24bit") = var("COLORTERM").as_deref() {
+            return AnsiColorMode::TrueColor;
+        }
+        match var("TERM").as_deref() {
+            None | Some("dumb") => AnsiColorMode::None,
+            Some(term) if term.contains("truecolor") || term.contains("direct") => {
+                AnsiColorMode::TrueColor
+            }
+            Some(term) if term.contains("256") => AnsiColorMode::Ansi256,
+            Some(_) => AnsiColorMode::Ansi16,
+        }
+    }
+}
+
+/// Render runs with ansi escape codes, only writing what changes between runs
+pub(crate) fn runs_to_ansi(runs: &[TextRun], mode: AnsiColorMode) -> String {
+    let mut output = String::new();
+    let mut current = Style::default();
+    for run in runs {
+        let mut parameters = Vec::new();
+        if run.style.color != current.color && mode != AnsiColorMode::None {
+            parameters.push(
+                run.style
+                    .color
+                    .and_then(|color| color.into_ansi_color_parameters(mode))
+                    .unwrap_or_else(|| "39".to_owned()),
+            );
+        }
+        for format in FORMATS {
+            match (current.has_format(format), run.style.has_format(format)) {
+                (false, true) => parameters.push(format.into_ansi_display_attribute().to_string()),
+                (true, false) => {
+                    parameters.push(format.into_ansi_reset_display_attribute().to_string())
+                }
+                _ => {}
+            }
+        }
+        if !parameters.is_empty() {
+            output.push_str(&format!("\x1B[{}m", parameters.join(";")));
+        }
+        current = run.style.clone();
+        output.push_str(&run.text);
+    }
+
+    let has_color_or_format = (current.color.is_some() && mode != AnsiColorMode::None)
+        || FORMATS.iter().any(|format| current.has_format(*format));
+    if has_color_or_format {
+        output.push_str("\x1B[0m");
+    }
+    output
+}
+
+/// The named color with exactly this rgb value, otherwise a hex color
+fn exact_color(rgb: [u8; 3]) -> ChatColor {
+    ChatColor::NAMED
+        .into_iter()
+        .find(|named| named.rgb() == Some(rgb))
+        .unwrap_or(ChatColor::Hex(rgb))
+}
+
+/// The named color rendered with this 16 color parameter (like 31 or 91)
+fn named_color(parameter: u16) -> Option<ChatColor> {
+    let parameter = parameter.to_string();
+    ChatColor::NAMED.into_iter().find(|named| {
+        named.into_ansi_color_parameters(AnsiColorMode::Ansi16) == Some(parameter.clone())
+    })
+}
+
+/// Read an extended color (after 38 or 48), consuming its parameters
+fn extended_color(parameters: &mut impl Iterator<Item = u16>) -> Option<ChatColor> {
+    match parameters.next()? {
+        5 => {
+            let index = u8::try_from(parameters.next()?).ok()?;
+            match index {
+                0..=7 => named_color(30 + index as u16),
+                8..=15 => named_color(90 + index as u16 - 8),
+                _ => Some(exact_color(color::xterm_256_rgb(index))),
+            }
+        }
+        2 => {
+            let mut rgb = [0; 3];
+            for channel in &mut rgb {
+                *channel = u8::try_from(parameters.next()?).ok()?;
+            }
+            Some(exact_color(rgb))
+        }
+        _ => None,
+    }
+}
+
+/// Apply the parameters of a SGR sequence (like "1;38;5;208") to the style
+fn apply_sgr(style: &mut Style, parameters: &str) {
+    // Missing parameters (like in "\x1B[m" or "\x1B[;1m") mean 0
+    let mut parameters = parameters
+        .split(';')
+        .map(|parameter| parameter.split(':').next().unwrap_or_default())
+        .map(|parameter| parameter.parse::<u16>().unwrap_or(0));
+    while let Some(parameter) = parameters.next() {
+        match parameter {
+            0 => *style = Style::default(),
+            1 => style.bold = true,
+            3 => style.italic = true,
+            4 | 21 => style.underlined = true,
+            8 => style.obfuscated = true,
+            9 => style.strikethrough = true,
+            22 => style.bold = false,
+            23 => style.italic = false,
+            24 => style.underlined = false,
+            28 => style.obfuscated = false,
+            29 => style.strikethrough = false,
+            30..=37 | 90..=97 => style.color = named_color(parameter),
+            38 => {
+                if let Some(color) = extended_color(&mut parameters) {
+                    style.color = Some(color);
+                }
+            }
+            39 => style.color = None,
+            // Skip the parameters of background and underline colors
+            48 | 58 => {
+                extended_color(&mut parameters);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Chat {
+    /// Parse text with ansi escape codes (like colored console output). Colors and formats of
+    /// SGR sequences are kept, every other escape sequence gets removed.
+    pub fn from_ansi(ansi_text: &str) -> Chat {
+        let mut runs = Vec::new();
+        let mut style = Style::default();
+        let mut rest = ansi_text;
+        let is_intermediate = |chr: char| ('\x20'..='\x2F').contains(&chr);
+        while let Some(escape) = rest.find('\x1B') {
+            push_run(&mut runs, &rest[..escape], &style);
+            rest = &rest[escape + 1..];
+            let length = if let Some(sequence) = rest.strip_prefix('[') {
+                // CSI: Parameter and intermediate bytes followed by a final byte
+                match sequence.find(|chr: char| ('\x40'..='\x7E').contains(&chr)) {
+                    Some(end) => {
+                        if sequence[end..].starts_with('m') {
+                            apply_sgr(&mut style, &sequence[..end]);
+                        }
+                        1 + end + 1
+                    }
+                    None => rest.len(),
+                }
+            } else if let Some(sequence) = rest.strip_prefix(']') {
+                // OSC (like titles and hyperlinks): Terminated by BEL or ESC \
+                match sequence.find(['\x07', '\x1B']) {
+                    Some(end) if sequence[end..].starts_with('\x07') => 1 + end + 1,
+                    Some(end) if sequence[end..].starts_with("\x1B\\") => 1 + end + 2,
+                    Some(end) => 1 + end,
+                    None => rest.len(),
+                }
+            } else if rest.starts_with(is_intermediate) {
+                // nF (like character set switches): Intermediate bytes followed by a final byte
+                let end = rest.find(|chr| !is_intermediate(chr)).unwrap_or(rest.len());
+                match rest[end..].starts_with(|chr: char| ('\x30'..='\x7E').contains(&chr)) {
+                    true => end + 1,
+                    false => end,
+                }
+            } else {
+                // Single char escapes (like ESC 7 and ESC 8 for saving and restoring the cursor)
+                rest.chars().next().map_or(0, char::len_utf8)
+            };
+            rest = &rest[length..];
+        }
+        push_run(&mut runs, rest, &style);
+        runs_to_chat(&runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat::{Chat, TextFormatter},
+        translator::Translator,
+    };
+
+    #[test]
+    fn test_ansi() {
+        let chat: Chat = serde_json::from_value(serde_json::json!({
+            "text": "a",
+            "color": "#FF0080",
+            "underlined": true,
+            "extra": [
+                { "text": "b" },
+                { "text": "c", "underlined": false, "bold": true },
+                { "text": "d", "color": "reset", "bold": false }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            chat.to_ansi_string(&Translator::default()),
+            "\x1B[38;2;255;0;128;4mab\x1B[1;24mc\x1B[39;22;4md\x1B[0m"
+        );
+    }
+
+    #[test]
+    fn test_color_modes() {
+        let chat = Chat::from_legacy("§a§lHi §rthere");
+        assert_eq!(
+            chat.to_ansi_string_with(&Translator::default(), AnsiColorMode::None),
+            "\x1B[1mHi \x1B[22mthere"
+        );
+    }
+
+    #[test]
+    fn test_detect() {
+        let detect = |vars: &[(&str, &str)]| {
+            AnsiColorMode::detect_with(|name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+        assert_eq!(
+            detect(&[("NO_COLOR", "1"), ("COLORTERM", "truecolor")]),
+            AnsiColorMode::None
+        );
+        assert_eq!(
+            detect(&[("COLORTERM", "truecolor"), ("TERM", "xterm")]),
+            AnsiColorMode::TrueColor
+        );
+        assert_eq!(
+            detect(&[("TERM", "xterm-256color")]),
+            AnsiColorMode::Ansi256
+        );
+        assert_eq!(detect(&[("TERM", "linux")]), AnsiColorMode::Ansi16);
+        assert_eq!(detect(&[("TERM", "dumb")]), AnsiColorMode::None);
+    }
+
+    #[test]
+    fn test_from_ansi() {
+        let chat = Chat::from_ansi(
+            "\x1B]0;title\x07\x1B[2K[\x1B[1;31mERROR\x1B[22;39m] \x1B[38;5;208;48;2;1;2;3mheated\x1B[38;5;9m red \x1B[38;2;85;255;85;3mgreen\x1B[m\x1B[8mhidden\x1B[0m",
+        );
+        assert_eq!(
+            chat.to_minimessage(),
+            "[<dark_red><bold>ERROR</bold></dark_red>] <#ff8700>heated</#ff8700><red> red </red><green><italic>green</italic></green><obfuscated>hidden"
+        );
+        let chat = Chat::from_legacy("§6§lGold §x§1§2§3§4§5§6§mhex§r§oitalic");
+        assert_eq!(
+            Chat::from_ansi(&chat.to_ansi_string(&Translator::default())),
+            Chat::from_legacy("§6§lGold §x§1§2§3§4§5§6§mhex§r§oitalic")
+        );
+
+        // Character set switches and single char escapes leave nothing behind
+        let translator = Translator::default();
+        assert_eq!(
+            Chat::from_ansi("a\x1B(Bb").to_plain_string(&translator),
+            "ab"
+        );
+        assert_eq!(
+            Chat::from_ansi("\x1B7a\x1B)0\x1B8b\x1B#8c").to_plain_string(&translator),
+            "abc"
+        );
+    }
+}
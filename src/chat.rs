@@ -1,24 +1,134 @@
-use std::collections::HashSet;
-
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::{
-    formatting::{ChatColor, ChatFormat},
-    translator::Translator,
+    ansi::AnsiColorMode,
+    formatting::ChatColor,
+    hover::HoverEvent,
+    html::HtmlStyles,
+    irc::IrcFormatting,
+    legacy::{LegacyDialect, LegacyHexColors},
+    markdown::DiscordMarkdown,
+    style::{push_legacy_runs, push_run, Style, TextRun},
+    translator::TranslationProvider,
 };
 
 pub trait TextFormatter {
+    /// Append the text of self as runs of the same effective style, inheriting from the parent style
+    fn append_text_runs(
+        &self,
+        translator: &dyn TranslationProvider,
+        parent_style: &Style,
+        runs: &mut Vec<TextRun>,
+    );
+
+    /// Resolve into runs of text with the same effective style
+    fn to_text_runs(&self, translator: &dyn TranslationProvider) -> Vec<TextRun> {
+        let mut runs = Vec::new();
+        self.append_text_runs(translator, &Style::default(), &mut runs);
+        runs
+    }
+
     /// Convert self into a legacy formatted string (using formatting codes prefixed by a paragraph "§")
-    fn to_legacy_string(&self, translator: &Translator) -> String;
+    /// Hex colors are replaced with the closest named color.
+    fn to_legacy_string(&self, translator: &dyn TranslationProvider) -> String {
+        self.to_legacy_string_with(
+            translator,
+            &LegacyDialect::section(),
+            LegacyHexColors::default(),
+        )
+    }
 
-    /// Similar to legacy string, but uses common ansi escape codes to render with colors in most terminals
-    fn to_ansi_string(&self, translator: &Translator) -> String {
-        crate::legacy_to_ansi(&self.to_legacy_string(translator))
+    /// Like [TextFormatter::to_legacy_string], but using the given syntax and handling of hex colors
+    fn to_legacy_string_with(
+        &self,
+        translator: &dyn TranslationProvider,
+        dialect: &LegacyDialect,
+        hex_colors: LegacyHexColors,
+    ) -> String {
+        crate::legacy::runs_to_legacy(
+            &self.to_text_runs(translator),
+            &Style::default(),
+            dialect,
+            hex_colors,
+        )
     }
 
-    /// Get string without any formatting
-    fn to_plain_string(&self, translator: &Translator) -> String {
-        crate::legacy_to_plain(&self.to_legacy_string(translator))
+    /// Uses common ansi escape codes to render with colors (including hex ones) in most terminals
+    fn to_ansi_string(&self, translator: &dyn TranslationProvider) -> String {
+        self.to_ansi_string_with(translator, AnsiColorMode::TrueColor)
+    }
+
+    /// Like [TextFormatter::to_ansi_string], but limited to the colors the given mode supports
+    fn to_ansi_string_with(
+        &self,
+        translator: &dyn TranslationProvider,
+        mode: AnsiColorMode,
+    ) -> String {
+        crate::ansi::runs_to_ansi(&self.to_text_runs(translator), mode)
+    }
+
+    /// Html with colors and decorations, hover text as tooltips and links for urls
+    fn to_html_string(&self, translator: &dyn TranslationProvider) -> String {
+        self.to_html_string_with(translator, HtmlStyles::Inline)
+    }
+
+    /// Like [TextFormatter::to_html_string], but styled the given way
+    fn to_html_string_with(
+        &self,
+        translator: &dyn TranslationProvider,
+        styles: HtmlStyles,
+    ) -> String {
+        crate::html::runs_to_html(&self.to_text_runs(translator), translator, styles)
+    }
+
+    /// Discord Markdown, with links for urls. Colors are dropped.
+    fn to_markdown_string(&self, translator: &dyn TranslationProvider) -> String {
+        self.to_markdown_string_with(translator, &DiscordMarkdown::default())
+    }
+
+    /// Like [TextFormatter::to_markdown_string], but with the given options (like formats for colors)
+    fn to_markdown_string_with(
+        &self,
+        translator: &dyn TranslationProvider,
+        options: &DiscordMarkdown,
+    ) -> String {
+        crate::markdown::runs_to_markdown(&self.to_text_runs(translator), options)
+    }
+
+    /// Text with mIRC formatting codes for relaying to IRC
+    fn to_irc_string(&self, translator: &dyn TranslationProvider) -> String {
+        self.to_irc_string_with(translator, &IrcFormatting::default())
+    }
+
+    /// Like [TextFormatter::to_irc_string], but with the given color table
+    fn to_irc_string_with(
+        &self,
+        translator: &dyn TranslationProvider,
+        options: &IrcFormatting,
+    ) -> String {
+        crate::irc::runs_to_irc(&self.to_text_runs(translator), options)
+    }
+
+    /// Get string without any formatting. Text of literal components is kept as is,
+    /// even if it contains "§".
+    fn to_plain_string(&self, translator: &dyn TranslationProvider) -> String {
+        self.to_plain_string_with(translator, false)
+    }
+
+    /// Like [TextFormatter::to_plain_string], but optionally also stripping legacy codes inside
+    /// of literal text, like the client does when displaying it
+    fn to_plain_string_with(
+        &self,
+        translator: &dyn TranslationProvider,
+        strip_legacy_codes: bool,
+    ) -> String {
+        self.to_text_runs(translator)
+            .iter()
+            .map(|run| match strip_legacy_codes {
+                true => crate::legacy::legacy_to_plain(&run.text),
+                false => run.text.clone(),
+            })
+            .collect()
     }
 }
 
@@ -26,6 +136,27 @@ fn is_false(b: &bool) -> bool {
     !b
 }
 
+/// Booleans are stored as bytes in NBT. Like vanilla, accept any number (non-zero being true).
+fn bool_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrNumber {
+        Bool(bool),
+        Number(f64),
+    }
+
+    Ok(match BoolOrNumber::deserialize(deserializer)? {
+        BoolOrNumber::Bool(value) => value,
+        BoolOrNumber::Number(value) => value != 0.0,
+    })
+}
+
+fn optional_bool_or_number<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<bool>, D::Error> {
+    bool_or_number(deserializer).map(Some)
+}
+
 fn default_separator() -> ChatComponent {
     ChatComponent {
         color: Some(ChatColor::Gray),
@@ -47,16 +178,36 @@ pub struct ChatComponent {
     #[serde(flatten)]
     pub content: TextContent,
 
-    #[serde(default, skip_serializing_if = "is_false")]
-    pub bold: bool,
-    #[serde(default, skip_serializing_if = "is_false")]
-    pub italic: bool,
-    #[serde(default, skip_serializing_if = "is_false")]
-    pub underlined: bool,
-    #[serde(default, skip_serializing_if = "is_false")]
-    pub strikethrough: bool,
-    #[serde(default, skip_serializing_if = "is_false")]
-    pub obfuscated: bool,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_bool_or_number"
+    )]
+    pub bold: Option<bool>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_bool_or_number"
+    )]
+    pub italic: Option<bool>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_bool_or_number"
+    )]
+    pub underlined: Option<bool>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_bool_or_number"
+    )]
+    pub strikethrough: Option<bool>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "optional_bool_or_number"
+    )]
+    pub obfuscated: Option<bool>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub color: Option<ChatColor>,
@@ -68,9 +219,11 @@ pub struct ChatComponent {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub font: Option<String>,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Also read from `click_event` (1.21.5 and newer)
+    #[serde(alias = "click_event", skip_serializing_if = "Option::is_none")]
     pub click_event: Option<ClickEvent>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Also read from `hover_event` (1.21.5 and newer)
+    #[serde(alias = "hover_event", skip_serializing_if = "Option::is_none")]
     pub hover_event: Option<HoverEvent>,
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -78,76 +231,44 @@ pub struct ChatComponent {
 }
 
 impl TextFormatter for ChatComponent {
-    fn to_legacy_string(&self, translator: &Translator) -> String {
-        let mut output = String::new();
-        let mut component_formatting = String::new();
-        if let Some(color) = self.color {
-            if let Some(color_code) = color.into_color_code() {
-                component_formatting.push('§');
-                component_formatting.push(color_code);
+    fn append_text_runs(
+        &self,
+        translator: &dyn TranslationProvider,
+        parent_style: &Style,
+        runs: &mut Vec<TextRun>,
+    ) {
+        let style = parent_style.inherit(self);
+        match &self.content {
+            TextContent::Literal { text } => push_run(runs, text, &style),
+            TextContent::Keybind { keybind } => {
+                push_run(runs, &format!("<keybind:{:?}>", keybind), &style)
+            }
+            TextContent::Nbt { .. } => push_run(runs, "<nbt>", &style),
+            TextContent::ScoreboardValue { score } => {
+                push_run(runs, &format!("<sbvalue:{:?}>", score.name), &style)
             }
-        }
-        if self.bold {
-            component_formatting.push_str("§l");
-        }
-        if self.italic {
-            component_formatting.push_str("§o");
-        }
-        if self.strikethrough {
-            component_formatting.push_str("§m");
-        }
-        if self.underlined {
-            component_formatting.push_str("§n");
-        }
-        if self.obfuscated {
-            component_formatting.push_str("§k");
-        }
-        output.push_str(&component_formatting);
-        output.push_str(&match &self.content {
-            TextContent::Literal { text } => text.to_owned(),
-            TextContent::Keybind { keybind } => format!("<keybind:{:?}>", keybind),
-            TextContent::Nbt { .. } => "<nbt>".to_owned(),
-            TextContent::ScoreboardValue { score } => format!("<sbvalue:{:?}>", score.name),
             TextContent::EntityNamesSelector { selector, .. } => {
-                format!("<selector:{:?}>", selector)
+                push_run(runs, &format!("<selector:{:?}>", selector), &style)
             }
             TextContent::Translatable {
                 translate,
                 with,
                 fallback,
             } => {
-                let resolved_args = with
-                    .as_ref()
-                    .unwrap_or(&Vec::new())
-                    .iter()
-                    .map(|arg| {
-                        // Seems mc expects the old formatting to be restored (not trusting args)
-                        format!(
-                            "{}{}",
-                            arg.to_legacy_string(translator),
-                            &component_formatting
-                        )
-                    })
-                    .collect::<Vec<_>>();
-                translator.translate(
-                    &translate,
-                    &resolved_args
-                        .iter()
-                        .map(|arg| arg.as_str())
-                        .collect::<Vec<_>>(),
-                    fallback.as_ref().map(|s| s.as_str()),
-                )
+                let args = with.as_deref().unwrap_or_default();
+                translator
+                    .translate_chat(translate, args, fallback.as_deref())
+                    .append_text_runs(translator, &style, runs);
             }
-        });
-        output.push_str("§r");
+        }
 
         for extra in &self.extra {
-            output.push_str(&extra.to_legacy_string(translator));
+            extra.append_text_runs(translator, &style, runs);
         }
-        output
     }
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum TranslatablePlaceholder {
@@ -158,10 +279,15 @@ pub enum TranslatablePlaceholder {
 }
 
 impl TextFormatter for TranslatablePlaceholder {
-    fn to_legacy_string(&self, translator: &Translator) -> String {
+    fn append_text_runs(
+        &self,
+        translator: &dyn TranslationProvider,
+        parent_style: &Style,
+        runs: &mut Vec<TextRun>,
+    ) {
         match self {
-            Self::Chat(chat) => chat.to_legacy_string(translator),
-            Self::Number(number) => number.to_string(),
+            Self::Chat(chat) => chat.append_text_runs(translator, parent_style, runs),
+            Self::Number(number) => push_run(runs, &number.to_string(), parent_style),
         }
     }
 }
@@ -180,7 +306,11 @@ pub enum TextContent {
     Nbt {
         nbt: String,
 
-        #[serde(default, skip_serializing_if = "is_false")]
+        #[serde(
+            default,
+            skip_serializing_if = "is_false",
+            deserialize_with = "bool_or_number"
+        )]
         interpret: bool,
         #[serde(default, skip_serializing_if = "is_default_separator")]
         separator: Option<Box<ChatComponent>>,
@@ -207,9 +337,9 @@ pub enum TextContent {
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Score {
-    name: String,
-    objective: String,
-    value: Option<i32>,
+    pub name: String,
+    pub objective: String,
+    pub value: Option<i32>,
 }
 
 impl Default for TextContent {
@@ -238,7 +368,7 @@ impl TextContent {
             TextContent::Translatable {
                 translate: translate.to_owned(),
                 with: Some(Vec::from_iter(with.iter().map(|arg| {
-                    TranslatablePlaceholder::Chat(Chat::Legacy(arg.to_string()))
+                    TranslatablePlaceholder::Chat(Chat::Text(arg.to_string()))
                 }))),
                 fallback: None,
             }
@@ -246,10 +376,54 @@ impl TextContent {
     }
 }
 
+/// Pages are numbers since 1.21.5
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(i64),
+    }
+
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(value) => value,
+        StringOrNumber::Number(value) => value.to_string(),
+    })
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ClickEvent {
-    action: ClickAction,
-    value: String,
+    pub action: ClickAction,
+    /// Since 1.21.5, this is named after the action (`url`, `command`, `path` or `page`)
+    #[serde(
+        alias = "url",
+        alias = "command",
+        alias = "path",
+        alias = "page",
+        deserialize_with = "string_or_number"
+    )]
+    pub value: String,
+}
+
+impl ClickEvent {
+    /// The json of this event with the value named after the action (1.21.5 and newer)
+    pub fn to_inline_json_value(&self) -> serde_json::Value {
+        let (key, value): (&str, serde_json::Value) = match self.action {
+            ClickAction::OpenUrl => ("url", self.value.as_str().into()),
+            ClickAction::OpenFile => ("path", self.value.as_str().into()),
+            ClickAction::RunCommand | ClickAction::SuggestCommand => {
+                ("command", self.value.as_str().into())
+            }
+            ClickAction::ChangePage => (
+                "page",
+                self.value
+                    .parse::<i32>()
+                    .map_or_else(|_| self.value.as_str().into(), serde_json::Value::from),
+            ),
+            ClickAction::CopyToClipboard => ("value", self.value.as_str().into()),
+        };
+        serde_json::json!({ "action": self.action, key: value })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -263,41 +437,35 @@ pub enum ClickAction {
     CopyToClipboard,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct HoverEvent {
-    action: HoverAction,
-    #[serde(flatten, alias = "value")]
-    contents: HoverContent,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum HoverContent {
-    /// Depending on action either legacy text or SNBT
-    Text(String),
-    /// Might be something unimplemented here, or also a ChatComponent
-    Json(serde_json::Value),
-}
-
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Chat {
+    /// A bare string, which is literal text (formatting codes in it are not interpreted)
+    Text(String),
+    /// Text with legacy formatting codes. Bare strings never deserialize to this.
     Legacy(String),
     Component(ChatComponent),
     Components(Vec<ChatComponent>),
 }
 
 impl TextFormatter for Chat {
-    fn to_legacy_string(&self, translator: &Translator) -> String {
+    fn append_text_runs(
+        &self,
+        translator: &dyn TranslationProvider,
+        parent_style: &Style,
+        runs: &mut Vec<TextRun>,
+    ) {
         match self {
-            Chat::Legacy(text) => text.to_owned(),
-            Chat::Component(component) => component.to_legacy_string(translator),
+            Chat::Text(text) => push_run(runs, text, parent_style),
+            Chat::Legacy(text) => push_legacy_runs(runs, text, parent_style),
+            Chat::Component(component) => {
+                component.append_text_runs(translator, parent_style, runs)
+            }
             Chat::Components(components) => {
-                let mut builder = String::new();
                 for component in components {
-                    builder.push_str(&component.to_legacy_string(translator));
+                    component.append_text_runs(translator, parent_style, runs);
                 }
-                builder
             }
         }
     }
@@ -308,90 +476,58 @@ impl Chat {
         Self::from_legacy(legacy_text)
     }
 
-    pub fn from_legacy(legacy_text: &str) -> Self {
-        let mut components: Vec<ChatComponent> = vec![];
-
-        let mut cur_text = String::new();
-        let mut cur_color: Option<ChatColor> = None;
-        let mut cur_formattings: HashSet<ChatFormat> = HashSet::default();
-
-        fn to_component(
-            text: &str,
-            color: &Option<ChatColor>,
-            formattings: &HashSet<ChatFormat>,
-        ) -> ChatComponent {
-            ChatComponent {
-                content: TextContent::Literal {
-                    text: text.to_owned(),
-                },
-                color: color.clone(),
-                bold: formattings.contains(&ChatFormat::Bold),
-                italic: formattings.contains(&ChatFormat::Italic),
-                obfuscated: formattings.contains(&ChatFormat::Obfuscated),
-                strikethrough: formattings.contains(&ChatFormat::Strikethrough),
-                underlined: formattings.contains(&ChatFormat::Underlined),
+    /// Turn any variant into a single component (further components get appended to extra)
+    pub fn into_component(self) -> ChatComponent {
+        match self {
+            Chat::Text(text) => ChatComponent {
+                content: TextContent::Literal { text },
                 ..Default::default()
+            },
+            Chat::Legacy(text) => Chat::from_legacy(&text).into_component(),
+            Chat::Component(component) => component,
+            Chat::Components(components) => {
+                let mut components = components.into_iter();
+                let mut root = components.next().unwrap_or_default();
+                root.extra.extend(components.map(Chat::Component));
+                root
             }
         }
-
-        let mut previous_was_paragraph = false;
-        for char in legacy_text.chars() {
-            if char == '§' {
-                previous_was_paragraph = true;
-                continue;
-            }
-            if previous_was_paragraph {
-                previous_was_paragraph = false;
-
-                if let Ok(format) = ChatFormat::from_format_code_char(char) {
-                    if !cur_text.is_empty() {
-                        components.push(to_component(&cur_text, &cur_color, &cur_formattings));
-                        cur_text.clear();
-                    }
-                    cur_formattings.insert(format);
-                }
-                if let Ok(color) = ChatColor::from_color_code_char(char) {
-                    if !cur_text.is_empty() {
-                        components.push(to_component(&cur_text, &cur_color, &cur_formattings));
-                        cur_text.clear();
-                    }
-                    cur_color = Some(color);
-                    cur_formattings.clear();
-                }
-                continue;
-            }
-
-            cur_text.push(char);
-        }
-
-        if !cur_text.is_empty() {
-            components.push(to_component(&cur_text, &cur_color, &cur_formattings));
-        }
-
-        let mut root_component = Default::default();
-        for (i, component) in components.into_iter().enumerate() {
-            if i == 0 {
-                root_component = component;
-            } else {
-                root_component.extra.push(Chat::Component(component));
-            }
-        }
-
-        return Chat::Component(root_component);
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum HoverAction {
-    ShowText,
-    ShowItem,
-    ShowEntity,
+    pub fn from_legacy(legacy_text: &str) -> Self {
+        Self::from_legacy_dialect(legacy_text, &LegacyDialect::section())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::translator::Translator;
+
+    #[test]
+    fn test_legacy_string() {
+        let translator = Translator::default();
+        let chat: Chat = serde_json::from_value(serde_json::json!({
+            "text": "a",
+            "color": "red",
+            "bold": true,
+            "extra": [
+                { "text": "b" },
+                { "text": "c", "bold": false },
+                { "text": "d", "color": "red", "extra": [{ "text": "e", "color": "reset" }] },
+                { "translate": "%s says %s", "with": [{ "text": "Steve", "color": "gold" }, "hi"] }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            chat.to_legacy_string(&translator),
+            "§c§lab§cc§ld§r§le§6§lSteve§c§l says hi"
+        );
+        assert_eq!(
+            Chat::from_legacy("§c§lBold§r plain").to_legacy_string(&translator),
+            "§c§lBold§r plain"
+        );
+    }
 
     #[test]
     fn test_json_parsing() {
@@ -409,46 +545,38 @@ mod tests {
             .unwrap()
         );
         // Complex
-        // Note quite working yet. Need to improve nesting ChatComponent
-        // inside of HoverEvent. Raw Json Value seems to cause issues here.
-        /*
         assert_eq!(
             ChatComponent {
                 content: TextContent::new_literal("Hello "),
                 color: Some(ChatColor::Red),
-                italic: true,
-                bold: true,
+                italic: Some(true),
+                bold: Some(true),
                 extra: vec![
-                    ChatComponent {
+                    Chat::Component(ChatComponent {
                         content: TextContent::new_translatable(
                             "test.translate.me",
                             &["Arg1", "Arg2"]
                         ),
-                        hover_event: Some(HoverEvent {
-                            action: HoverAction::ShowText,
-                            contents: HoverContent::Json(
-                                serde_json::to_value(ChatComponent {
-                                    color: Some(ChatColor::DarkGreen),
-                                    content: TextContent::new_literal("beautiful"),
-                                    ..Default::default()
-                                })
-                                .unwrap()
-                            )
-                        }),
+                        hover_event: Some(HoverEvent::ShowText(Box::new(Chat::Component(
+                            ChatComponent {
+                                color: Some(ChatColor::DarkGreen),
+                                content: TextContent::new_literal("beautiful"),
+                                ..Default::default()
+                            }
+                        )))),
                         ..Default::default()
-                    },
-                    ChatComponent {
+                    }),
+                    Chat::Component(ChatComponent {
                         content: TextContent::new_literal(" World",),
-                        hover_event: Some(HoverEvent {
-                            action: HoverAction::ShowText,
-                            contents: HoverContent::Text("§aAnother hover text!".to_owned())
-                        }),
+                        hover_event: Some(HoverEvent::ShowText(Box::new(Chat::Text(
+                            "§aAnother hover text!".to_owned()
+                        )))),
                         click_event: Some(ClickEvent {
                             action: ClickAction::CopyToClipboard,
                             value: "You clicked the world!".to_owned()
                         }),
                         ..Default::default()
-                    }
+                    })
                 ],
                 ..Default::default()
             },
@@ -483,6 +611,6 @@ mod tests {
                 ]
             }))
             .unwrap()
-        );*/
+        );
     }
 }
@@ -0,0 +1,266 @@
+use crate::{
+    chat::{Chat, ChatComponent, TextContent, TranslatablePlaceholder},
+    hover::HoverEvent,
+};
+
+/// How the difference between two colors is measured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ColorDistance {
+    /// Euclidean distance of the raw rgb values. Fast, but not perceptually uniform.
+    Rgb,
+    /// Euclidean distance in [OKLab](https://bottosson.github.io/posts/oklab/)
+    #[default]
+    OkLab,
+    /// The CIEDE2000 color difference formula using CIELAB with a D65 white point
+    Ciede2000,
+}
+
+impl ColorDistance {
+    /// Difference between two colors (only comparable to other results of the same metric)
+    pub fn distance(self, a: [u8; 3], b: [u8; 3]) -> f64 {
+        let euclidean =
+            |a: [f64; 3], b: [f64; 3]| (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f64>().sqrt();
+        match self {
+            ColorDistance::Rgb => euclidean(a.map(f64::from), b.map(f64::from)),
+            ColorDistance::OkLab => euclidean(rgb_to_oklab(a), rgb_to_oklab(b)),
+            ColorDistance::Ciede2000 => ciede2000(rgb_to_cielab(a), rgb_to_cielab(b)),
+        }
+    }
+}
+
+fn srgb_to_linear(channel: u8) -> f64 {
+    let channel = channel as f64 / 255.0;
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert to [OKLab](https://bottosson.github.io/posts/oklab/), where euclidean distances
+/// are close to perceived differences
+pub(crate) fn rgb_to_oklab(rgb: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+/// Convert to CIELAB (D65 white point)
+fn rgb_to_cielab(rgb: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = (0.0193339 * r + 0.1191920 * g + 0.9503041 * b) / 1.08883;
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// See <https://en.wikipedia.org/wiki/Color_difference#CIEDE2000>
+fn ciede2000(lab1: [f64; 3], lab2: [f64; 3]) -> f64 {
+    let [l1, a1, b1] = lab1;
+    let [l2, a2, b2] = lab2;
+    let c_mean = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+    let g = 0.5 * (1.0 - (c_mean.powi(7) / (c_mean.powi(7) + 25f64.powi(7))).sqrt());
+    let (a1, a2) = (a1 * (1.0 + g), a2 * (1.0 + g));
+    let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+    let hue = |a: f64, b: f64| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let (h1, h2) = (hue(a1, b1), hue(a2, b2));
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let delta_h = if c1 * c2 == 0.0 {
+        0.0
+    } else if (h2 - h1).abs() <= 180.0 {
+        h2 - h1
+    } else if h2 <= h1 {
+        h2 - h1 + 360.0
+    } else {
+        h2 - h1 - 360.0
+    };
+    let delta_big_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+    let l_mean = (l1 + l2) / 2.0;
+    let c_mean = (c1 + c2) / 2.0;
+    let h_mean = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+    let t = 1.0 - 0.17 * (h_mean - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_mean).to_radians().cos()
+        + 0.32 * (3.0 * h_mean + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_mean - 63.0).to_radians().cos();
+    let s_l = 1.0 + 0.015 * (l_mean - 50.0).powi(2) / (20.0 + (l_mean - 50.0).powi(2)).sqrt();
+    let s_c = 1.0 + 0.045 * c_mean;
+    let s_h = 1.0 + 0.015 * c_mean * t;
+    let r_t = -2.0
+        * (c_mean.powi(7) / (c_mean.powi(7) + 25f64.powi(7))).sqrt()
+        * (60.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp())
+            .to_radians()
+            .sin();
+
+    ((delta_l / s_l).powi(2)
+        + (delta_c / s_c).powi(2)
+        + (delta_big_h / s_h).powi(2)
+        + r_t * (delta_c / s_c) * (delta_big_h / s_h))
+        .sqrt()
+}
+
+/// Index of the closest color in the palette
+pub(crate) fn nearest_index(rgb: [u8; 3], palette: &[[u8; 3]], metric: ColorDistance) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            metric
+                .distance(rgb, **a)
+                .total_cmp(&metric.distance(rgb, **b))
+        })
+        .map(|(index, _)| index)
+        .expect("Palette is not empty")
+}
+
+/// Rgb values of the xterm 256 color palette entries 16 to 255 (the first 16 depend on the terminal theme)
+pub(crate) fn xterm_256_rgb(index: u8) -> [u8; 3] {
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match index {
+        16..=231 => {
+            let index = index - 16;
+            [
+                CUBE_LEVELS[(index / 36) as usize],
+                CUBE_LEVELS[(index / 6 % 6) as usize],
+                CUBE_LEVELS[(index % 6) as usize],
+            ]
+        }
+        232..=255 => [8 + (index - 232) * 10; 3],
+        _ => panic!("Only 16 to 255 have well defined colors"),
+    }
+}
+
+impl Chat {
+    /// Replace all hex colors with the closest named color, for clients older than 1.16
+    pub fn downsample_colors(&mut self, metric: ColorDistance) {
+        match self {
+            Chat::Text(_) | Chat::Legacy(_) => {}
+            Chat::Component(component) => component.downsample_colors(metric),
+            Chat::Components(components) => components
+                .iter_mut()
+                .for_each(|component| component.downsample_colors(metric)),
+        }
+    }
+}
+
+impl ChatComponent {
+    /// Replace all hex colors with the closest named color, for clients older than 1.16
+    pub fn downsample_colors(&mut self, metric: ColorDistance) {
+        self.color = self.color.map(|color| color.to_named(metric));
+        match &mut self.content {
+            TextContent::Translatable {
+                with: Some(with), ..
+            } => {
+                for arg in with {
+                    if let TranslatablePlaceholder::Chat(chat) = arg {
+                        chat.downsample_colors(metric);
+                    }
+                }
+            }
+            TextContent::Nbt {
+                separator: Some(separator),
+                ..
+            }
+            | TextContent::EntityNamesSelector {
+                separator: Some(separator),
+                ..
+            } => separator.downsample_colors(metric),
+            _ => {}
+        }
+        match &mut self.hover_event {
+            Some(HoverEvent::ShowText(text)) => text.downsample_colors(metric),
+            Some(HoverEvent::ShowEntity(entity)) => {
+                if let Some(name) = &mut entity.name {
+                    name.downsample_colors(metric);
+                }
+            }
+            _ => {}
+        }
+        for extra in &mut self.extra {
+            extra.downsample_colors(metric);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatting::ChatColor;
+
+    #[test]
+    fn test_ciede2000() {
+        // Pairs from the test data by Sharma, Wu and Dalal
+        let cases = [
+            ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+            ([50.0, -1.3802, -84.2814], [50.0, 0.0, -82.7485], 1.0000),
+            ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+            (
+                [60.2574, -34.0099, 36.2677],
+                [60.4626, -34.1751, 39.4387],
+                1.2644,
+            ),
+            (
+                [2.0776, 0.0795, -1.1350],
+                [0.9033, -0.0636, -0.5514],
+                0.9082,
+            ),
+        ];
+        for (lab1, lab2, expected) in cases {
+            assert!((ciede2000(lab1, lab2) - expected).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn test_downsample() {
+        assert_eq!(
+            ChatColor::Hex([0xF0, 0x40, 0x40]).to_named(ColorDistance::OkLab),
+            ChatColor::Red
+        );
+        assert_eq!(
+            ChatColor::Hex([0x10, 0x10, 0x10]).to_named(ColorDistance::Rgb),
+            ChatColor::Black
+        );
+        assert_eq!(
+            ChatColor::Hex([0xA0, 0xA8, 0xA0]).to_named(ColorDistance::Ciede2000),
+            ChatColor::Gray
+        );
+
+        let mut chat = Chat::from_minimessage("<#00AA01>a<gold>b</gold><#5050F0>c");
+        chat.downsample_colors(ColorDistance::OkLab);
+        assert_eq!(chat.to_minimessage(), "<dark_green>a<gold>b</gold><blue>c");
+
+        let mut chat = Chat::from_minimessage("<hover:show_text:'<#F04040>hi'>a");
+        chat.downsample_colors(ColorDistance::OkLab);
+        assert_eq!(chat.to_minimessage(), "<hover:show_text:'<red>hi'>a");
+    }
+}
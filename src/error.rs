@@ -23,3 +23,126 @@ pub enum ChatFormatParseError {
     #[error("Invalid format code format (expected format like §X of lenght 2, found {found:?} of lenght {length})")]
     InvalidFormatCodeFormat { found: String, length: usize },
 }
+
+#[derive(Error, Debug)]
+pub enum NbtError {
+    #[error("Failed to read or write NBT")]
+    Io(#[from] std::io::Error),
+    #[error("{tag_type} is not a valid NBT tag type")]
+    InvalidTagType { tag_type: u8 },
+    #[error("Found an end tag where a value was expected")]
+    UnexpectedEndTag,
+    #[error("Invalid negative length {length}")]
+    NegativeLength { length: i32 },
+    #[error("String is not valid modified UTF-8")]
+    InvalidString,
+    #[error("String is too long (expected at most 65535 bytes, found {length})")]
+    StringTooLong { length: usize },
+    #[error("NBT is nested deeper than {max_depth} levels")]
+    TooDeep { max_depth: usize },
+    #[error("{value} can not be represented as NBT")]
+    UnrepresentableValue { value: String },
+    #[error("NBT is not a valid text component")]
+    InvalidComponent(#[from] serde_json::Error),
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SnbtError {
+    #[error("Unexpected end of input at line {line}, column {column}")]
+    UnexpectedEnd { line: usize, column: usize },
+    #[error("Expected {expected} at line {line}, column {column}, found {found:?}")]
+    UnexpectedChar {
+        expected: &'static str,
+        found: char,
+        line: usize,
+        column: usize,
+    },
+    #[error("Invalid escape sequence {sequence:?} at line {line}, column {column}")]
+    InvalidEscape {
+        sequence: String,
+        line: usize,
+        column: usize,
+    },
+    #[error("Invalid element for {array_type} array at line {line}, column {column}")]
+    InvalidArrayElement {
+        array_type: char,
+        line: usize,
+        column: usize,
+    },
+    #[error("Unexpected trailing data at line {line}, column {column}")]
+    TrailingData { line: usize, column: usize },
+    #[error("SNBT is nested deeper than {max_depth} levels at line {line}, column {column}")]
+    TooDeep {
+        max_depth: usize,
+        line: usize,
+        column: usize,
+    },
+    #[error("SNBT is not a valid text component: {message}")]
+    InvalidComponent { message: String },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum MiniMessageError {
+    #[error("Unknown tag {tag:?} at offset {offset}")]
+    UnknownTag { tag: String, offset: usize },
+    #[error("Invalid arguments for tag {tag:?} at offset {offset}")]
+    InvalidArguments { tag: String, offset: usize },
+    #[error("Closing tag {tag:?} at offset {offset} does not match any open tag")]
+    UnmatchedClosingTag { tag: String, offset: usize },
+    #[error("Tag {tag:?} opened at offset {offset} is not explicitly closed")]
+    UnclosedTag { tag: String, offset: usize },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LegacyError {
+    #[error("Unknown code {code:?} at offset {offset}")]
+    UnknownCode { code: char, offset: usize },
+    #[error("Prefix at offset {offset} is not followed by a code")]
+    DanglingPrefix { offset: usize },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum HtmlError {
+    #[error("Unsupported tag {tag:?} at offset {offset}")]
+    UnsupportedTag { tag: String, offset: usize },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TranslationError {
+    #[error("Unsupported format specifier {specifier:?} at offset {offset}")]
+    UnsupportedSpecifier { specifier: String, offset: usize },
+    #[error("Stray percent sign at offset {offset}")]
+    InvalidFormat { offset: usize },
+    #[error("Argument {position} referenced at offset {offset} does not exist")]
+    MissingArgument { position: usize, offset: usize },
+    #[error("Argument {position} is never used")]
+    UnusedArgument { position: usize },
+}
+
+#[derive(Error, Debug)]
+pub enum LocaleError {
+    #[error("Failed to read translations")]
+    Io(#[from] std::io::Error),
+    #[error("Translations for {locale} are not valid json")]
+    InvalidJson {
+        locale: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("Locale {locale:?} is not available")]
+    UnknownLocale { locale: String },
+}
+
+#[derive(Error, Debug)]
+pub enum ResourcePackError {
+    #[error("Failed to read resource pack")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to read resource pack zip")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("{path} is not valid json")]
+    InvalidJson {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
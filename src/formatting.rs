@@ -1,6 +1,10 @@
 use serde_with::{DeserializeFromStr, SerializeDisplay};
 
-use crate::error::{ChatColorParseError, ChatFormatParseError};
+use crate::{
+    ansi::AnsiColorMode,
+    color::{self, ColorDistance},
+    error::{ChatColorParseError, ChatFormatParseError},
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SerializeDisplay, DeserializeFromStr)]
 pub enum ChatColor {
@@ -26,6 +30,26 @@ pub enum ChatColor {
 }
 
 impl ChatColor {
+    /// All colors that have a name and legacy code
+    pub const NAMED: [ChatColor; 16] = [
+        ChatColor::Black,
+        ChatColor::DarkBlue,
+        ChatColor::DarkGreen,
+        ChatColor::DarkAqua,
+        ChatColor::DarkRed,
+        ChatColor::DarkPurple,
+        ChatColor::Gold,
+        ChatColor::Gray,
+        ChatColor::DarkGray,
+        ChatColor::Blue,
+        ChatColor::Green,
+        ChatColor::Aqua,
+        ChatColor::Red,
+        ChatColor::LightPurple,
+        ChatColor::Yellow,
+        ChatColor::White,
+    ];
+
     pub fn from_hex_str(hex_str: &str) -> Result<Self, ChatColorParseError> {
         if !hex_str.starts_with('#') || hex_str.len() != 7 {
             return Err(ChatColorParseError::InvalidHexFormat {
@@ -33,11 +57,11 @@ impl ChatColor {
             });
         };
         let r = u8::from_str_radix(&hex_str[1..3], 16)
-            .map_err(|err| ChatColorParseError::HexUnparsableInt(err))?;
+            .map_err(ChatColorParseError::HexUnparsableInt)?;
         let g = u8::from_str_radix(&hex_str[3..5], 16)
-            .map_err(|err| ChatColorParseError::HexUnparsableInt(err))?;
+            .map_err(ChatColorParseError::HexUnparsableInt)?;
         let b = u8::from_str_radix(&hex_str[5..7], 16)
-            .map_err(|err| ChatColorParseError::HexUnparsableInt(err))?;
+            .map_err(ChatColorParseError::HexUnparsableInt)?;
         Ok(ChatColor::Hex([r, g, b]))
     }
 
@@ -126,36 +150,90 @@ impl ChatColor {
         })
     }
 
-    pub fn into_ansi_escape_code(self, reset_formatting: bool) -> String {
-        let simple_color =
-            |reset, color| format!("\x1B[{}{}m", if reset { "0;" } else { "" }, color);
+    /// The color as rendered by the vanilla client (none for reset)
+    pub fn rgb(self) -> Option<[u8; 3]> {
+        Some(match self {
+            ChatColor::Black => [0x00, 0x00, 0x00],
+            ChatColor::DarkBlue => [0x00, 0x00, 0xAA],
+            ChatColor::DarkGreen => [0x00, 0xAA, 0x00],
+            ChatColor::DarkAqua => [0x00, 0xAA, 0xAA],
+            ChatColor::DarkRed => [0xAA, 0x00, 0x00],
+            ChatColor::DarkPurple => [0xAA, 0x00, 0xAA],
+            ChatColor::Gold => [0xFF, 0xAA, 0x00],
+            ChatColor::Gray => [0xAA, 0xAA, 0xAA],
+            ChatColor::DarkGray => [0x55, 0x55, 0x55],
+            ChatColor::Blue => [0x55, 0x55, 0xFF],
+            ChatColor::Green => [0x55, 0xFF, 0x55],
+            ChatColor::Aqua => [0x55, 0xFF, 0xFF],
+            ChatColor::Red => [0xFF, 0x55, 0x55],
+            ChatColor::LightPurple => [0xFF, 0x55, 0xFF],
+            ChatColor::Yellow => [0xFF, 0xFF, 0x55],
+            ChatColor::White => [0xFF, 0xFF, 0xFF],
+            ChatColor::Reset => return None,
+            ChatColor::Hex(rgb) => rgb,
+        })
+    }
+
+    /// The closest named color for hex colors (others stay the same)
+    pub fn to_named(self, metric: ColorDistance) -> Self {
         match self {
-            ChatColor::Black => simple_color(reset_formatting, 30),
-            ChatColor::DarkBlue => simple_color(reset_formatting, 34),
-            ChatColor::DarkGreen => simple_color(reset_formatting, 32),
-            ChatColor::DarkAqua => simple_color(reset_formatting, 36),
-            ChatColor::DarkRed => simple_color(reset_formatting, 31),
-            ChatColor::DarkPurple => simple_color(reset_formatting, 35),
-            ChatColor::Gold => simple_color(reset_formatting, 33),
-            ChatColor::Gray => simple_color(reset_formatting, 37),
-            ChatColor::DarkGray => simple_color(reset_formatting, 90),
-            ChatColor::Blue => simple_color(reset_formatting, 94),
-            ChatColor::Green => simple_color(reset_formatting, 92),
-            ChatColor::Aqua => simple_color(reset_formatting, 96),
-            ChatColor::Red => simple_color(reset_formatting, 91),
-            ChatColor::LightPurple => simple_color(reset_formatting, 95),
-            ChatColor::Yellow => simple_color(reset_formatting, 93),
-            ChatColor::White => simple_color(reset_formatting, 97),
-            ChatColor::Reset => format!("\x1B[0m"),
             ChatColor::Hex(rgb) => {
-                return format!(
-                    "\x1B[{}38;2;{};{};{}m",
-                    if reset_formatting { "0;" } else { "" },
-                    rgb[0],
-                    rgb[1],
-                    rgb[2]
+                let palette = ChatColor::NAMED.map(|named| named.rgb().unwrap());
+                ChatColor::NAMED[color::nearest_index(rgb, &palette, metric)]
+            }
+            color => color,
+        }
+    }
+
+    /// The SGR parameters selecting this as foreground color in the given mode
+    /// (none for reset or if the mode has no colors). Hex colors get mapped to the
+    /// perceptually closest palette entry, if the mode doesn't support them.
+    pub fn into_ansi_color_parameters(self, mode: AnsiColorMode) -> Option<String> {
+        let named_parameter = |color: ChatColor| match color {
+            ChatColor::Black => 30,
+            ChatColor::DarkBlue => 34,
+            ChatColor::DarkGreen => 32,
+            ChatColor::DarkAqua => 36,
+            ChatColor::DarkRed => 31,
+            ChatColor::DarkPurple => 35,
+            ChatColor::Gold => 33,
+            ChatColor::Gray => 37,
+            ChatColor::DarkGray => 90,
+            ChatColor::Blue => 94,
+            ChatColor::Green => 92,
+            ChatColor::Aqua => 96,
+            ChatColor::Red => 91,
+            ChatColor::LightPurple => 95,
+            ChatColor::Yellow => 93,
+            _ => 97,
+        };
+        Some(match (self, mode) {
+            (_, AnsiColorMode::None) | (ChatColor::Reset, _) => return None,
+            (ChatColor::Hex(rgb), AnsiColorMode::TrueColor) => {
+                format!("38;2;{};{};{}", rgb[0], rgb[1], rgb[2])
+            }
+            (ChatColor::Hex(rgb), AnsiColorMode::Ansi256) => {
+                let palette = (16..=255).map(color::xterm_256_rgb).collect::<Vec<_>>();
+                format!(
+                    "38;5;{}",
+                    16 + color::nearest_index(rgb, &palette, ColorDistance::OkLab)
                 )
             }
+            (ChatColor::Hex(_), AnsiColorMode::Ansi16) => {
+                named_parameter(self.to_named(ColorDistance::OkLab)).to_string()
+            }
+            (named, _) => named_parameter(named).to_string(),
+        })
+    }
+
+    pub fn into_ansi_escape_code(self, reset_formatting: bool) -> String {
+        match self.into_ansi_color_parameters(AnsiColorMode::TrueColor) {
+            Some(parameters) => format!(
+                "\x1B[{}{}m",
+                if reset_formatting { "0;" } else { "" },
+                parameters
+            ),
+            None => "\x1B[0m".to_owned(),
         }
     }
 }
@@ -253,6 +331,17 @@ impl ChatFormat {
         }
     }
 
+    /// The display attribute turning this format off again
+    pub fn into_ansi_reset_display_attribute(self) -> u8 {
+        match self {
+            ChatFormat::Bold => 22,
+            ChatFormat::Italic => 23,
+            ChatFormat::Underlined => 24,
+            ChatFormat::Strikethrough => 29,
+            ChatFormat::Obfuscated => 28,
+        }
+    }
+
     pub fn into_ansi_escape_code(self) -> String {
         format!("\x1B[{}m", self.into_ansi_display_attribute())
     }
@@ -279,6 +368,28 @@ mod tests {
         );
         "#-azxxxx".parse::<ChatColor>().unwrap_err();
     }
+
+    #[test]
+    fn test_ansi_color_modes() {
+        let orange = ChatColor::Hex([0xF0, 0x90, 0x20]);
+        assert_eq!(
+            orange.into_ansi_color_parameters(AnsiColorMode::TrueColor),
+            Some("38;2;240;144;32".to_owned())
+        );
+        assert_eq!(
+            orange.into_ansi_color_parameters(AnsiColorMode::Ansi256),
+            Some("38;5;208".to_owned())
+        );
+        assert_eq!(
+            orange.into_ansi_color_parameters(AnsiColorMode::Ansi16),
+            Some("33".to_owned())
+        );
+        assert_eq!(orange.into_ansi_color_parameters(AnsiColorMode::None), None);
+        assert_eq!(
+            ChatColor::Red.into_ansi_color_parameters(AnsiColorMode::Ansi256),
+            Some("91".to_owned())
+        );
+    }
     #[test]
 
     fn test_formats() {
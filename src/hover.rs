@@ -0,0 +1,572 @@
+use std::collections::BTreeMap;
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+
+use crate::{
+    chat::{Chat, ChatComponent, ClickEvent, TextContent},
+    nbt::NbtTag,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoverAction {
+    ShowText,
+    ShowItem,
+    ShowEntity,
+}
+
+/// How hover events get serialized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum HoverFormat {
+    /// `value` with items and entities as SNBT text (before 1.16)
+    Value,
+    /// `contents` with items and entities as objects (1.16 and newer)
+    #[default]
+    Contents,
+    /// `hover_event` and `click_event` with their fields inline (1.21.5 and newer)
+    Inline,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HoverEvent {
+    ShowText(Box<Chat>),
+    ShowItem(HoverItem),
+    ShowEntity(HoverEntity),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoverItem {
+    pub id: String,
+    pub count: i32,
+    /// Data components (1.20.5 and newer)
+    pub components: Option<Value>,
+    /// The nbt tag as SNBT (before 1.20.5)
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoverEntity {
+    pub entity_type: String,
+    pub id: Uuid,
+    pub name: Option<Box<Chat>>,
+}
+
+/// A UUID, which can be written as string or as array of four ints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Uuid(pub u128);
+
+impl Uuid {
+    /// Most significant int first, like in NBT
+    pub fn from_int_array(ints: [i32; 4]) -> Self {
+        Self(
+            ints.iter()
+                .fold(0, |uuid, int| (uuid << 32) | *int as u32 as u128),
+        )
+    }
+
+    pub fn to_int_array(self) -> [i32; 4] {
+        [96, 64, 32, 0].map(|shift| (self.0 >> shift) as u32 as i32)
+    }
+
+    /// Parse with or without hyphens
+    pub fn parse(uuid: &str) -> Option<Self> {
+        let hex = uuid.replace('-', "");
+        if hex.len() != 32 || !hex.chars().all(|chr| chr.is_ascii_hexdigit()) {
+            return None;
+        }
+        u128::from_str_radix(&hex, 16).ok().map(Self)
+    }
+}
+
+impl std::fmt::Display for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+impl Serialize for Uuid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Uuid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum StringOrInts {
+            String(String),
+            Ints([i32; 4]),
+        }
+
+        match StringOrInts::deserialize(deserializer)? {
+            StringOrInts::String(uuid) => {
+                Uuid::parse(&uuid).ok_or_else(|| D::Error::custom(format!("Invalid uuid {uuid:?}")))
+            }
+            StringOrInts::Ints(ints) => Ok(Uuid::from_int_array(ints)),
+        }
+    }
+}
+
+impl HoverEvent {
+    pub fn action(&self) -> HoverAction {
+        match self {
+            HoverEvent::ShowText(_) => HoverAction::ShowText,
+            HoverEvent::ShowItem(_) => HoverAction::ShowItem,
+            HoverEvent::ShowEntity(_) => HoverAction::ShowEntity,
+        }
+    }
+
+    /// The json of this event in the given format
+    pub fn to_json_value(&self, format: HoverFormat) -> Result<Value, serde_json::Error> {
+        if format == HoverFormat::Inline {
+            let mut event = match self {
+                HoverEvent::ShowText(text) => json!({ "value": text }),
+                HoverEvent::ShowItem(item) => {
+                    let mut event = json!({ "id": item.id });
+                    if item.count != 1 {
+                        event["count"] = item.count.into();
+                    }
+                    if let Some(components) = &item.components {
+                        event["components"] = components.clone();
+                    }
+                    event
+                }
+                HoverEvent::ShowEntity(entity) => {
+                    let mut event = json!({ "id": entity.entity_type, "uuid": entity.id });
+                    if let Some(name) = &entity.name {
+                        event["name"] = serde_json::to_value(name)?;
+                    }
+                    event
+                }
+            };
+            event["action"] = serde_json::to_value(self.action())?;
+            return Ok(event);
+        }
+
+        let contents = match self {
+            HoverEvent::ShowText(text) => serde_json::to_value(text)?,
+            HoverEvent::ShowItem(item) if format == HoverFormat::Value => {
+                let unrepresentable = |message: String| {
+                    <serde_json::Error as serde::ser::Error>::custom(format!(
+                        "Item {} can not be shown before 1.16: {message}",
+                        item.id
+                    ))
+                };
+                if item.components.is_some() {
+                    return Err(unrepresentable("Data components need 1.20.5".to_owned()));
+                }
+                // Counts were a byte back then
+                let count = i8::try_from(item.count)
+                    .map_err(|_| unrepresentable(format!("Count {} is no byte", item.count)))?;
+                let mut compound = BTreeMap::from([
+                    ("id".to_owned(), NbtTag::String(item.id.clone())),
+                    ("Count".to_owned(), NbtTag::Byte(count)),
+                ]);
+                if let Some(tag) = &item.tag {
+                    let tag = NbtTag::from_snbt(tag)
+                        .map_err(|error| unrepresentable(format!("Invalid tag: {error}")))?;
+                    compound.insert("tag".to_owned(), tag);
+                }
+                Value::String(NbtTag::Compound(compound).to_snbt())
+            }
+            HoverEvent::ShowItem(item) => {
+                let mut contents = json!({ "id": item.id });
+                if item.count != 1 {
+                    contents["count"] = item.count.into();
+                }
+                if let Some(components) = &item.components {
+                    contents["components"] = components.clone();
+                }
+                if let Some(tag) = &item.tag {
+                    contents["tag"] = tag.as_str().into();
+                }
+                contents
+            }
+            HoverEvent::ShowEntity(entity) if format == HoverFormat::Value => {
+                let mut compound = BTreeMap::from([
+                    (
+                        "type".to_owned(),
+                        NbtTag::String(entity.entity_type.clone()),
+                    ),
+                    ("id".to_owned(), NbtTag::String(entity.id.to_string())),
+                ]);
+                if let Some(name) = &entity.name {
+                    compound.insert(
+                        "name".to_owned(),
+                        NbtTag::String(serde_json::to_string(name)?),
+                    );
+                }
+                Value::String(NbtTag::Compound(compound).to_snbt())
+            }
+            HoverEvent::ShowEntity(entity) => {
+                let mut contents = json!({ "type": entity.entity_type, "id": entity.id });
+                if let Some(name) = &entity.name {
+                    contents["name"] = serde_json::to_value(name)?;
+                }
+                contents
+            }
+        };
+        let key = match format {
+            HoverFormat::Contents => "contents",
+            _ => "value",
+        };
+        Ok(json!({ "action": self.action(), key: contents }))
+    }
+
+    /// Read the `value` (before 1.16), `contents` (1.16 and newer) or inline fields (1.21.5 and newer)
+    /// of an event
+    fn from_contents(
+        action: HoverAction,
+        contents: Value,
+        is_legacy: bool,
+    ) -> Result<Self, String> {
+        let invalid =
+            |error: &dyn std::fmt::Display| format!("Invalid {action:?} hover event: {error}");
+        if action == HoverAction::ShowText {
+            return serde_json::from_value(contents)
+                .map(|text| HoverEvent::ShowText(Box::new(text)))
+                .map_err(|error| invalid(&error));
+        }
+
+        let contents = match contents {
+            // Items and entities were SNBT in a (text) component
+            contents if is_legacy => {
+                let mut snbt = String::new();
+                raw_text(
+                    &serde_json::from_value::<Chat>(contents).map_err(|error| invalid(&error))?,
+                    &mut snbt,
+                );
+                let mut contents = NbtTag::from_snbt(&snbt)
+                    .map_err(|error| invalid(&error))?
+                    .to_json_value()
+                    .map_err(|error| invalid(&error))?;
+                // The tag of items is an object in SNBT, but a string in contents
+                if let Some(tag) = contents.get("tag").cloned() {
+                    let tag = NbtTag::from_json_value(&tag).map_err(|error| invalid(&error))?;
+                    contents["tag"] = tag.to_snbt().into();
+                }
+                contents
+            }
+            // Just the id of an item
+            Value::String(id) if action == HoverAction::ShowItem => json!({ "id": id }),
+            contents => contents,
+        };
+        let field = |name: &str| contents.get(name).cloned();
+        let string = |name: &str| {
+            field(name)
+                .and_then(|value| value.as_str().map(str::to_owned))
+                .ok_or_else(|| invalid(&format!("Missing {name}")))
+        };
+
+        Ok(match action {
+            HoverAction::ShowItem => HoverEvent::ShowItem(HoverItem {
+                id: string("id")?,
+                count: match field("count").or_else(|| field("Count")) {
+                    Some(count) => count
+                        .as_i64()
+                        .and_then(|count| i32::try_from(count).ok())
+                        .ok_or_else(|| invalid(&format!("Invalid count {count}")))?,
+                    None => 1,
+                },
+                components: field("components"),
+                tag: field("tag").and_then(|tag| tag.as_str().map(str::to_owned)),
+            }),
+            _ => HoverEvent::ShowEntity(HoverEntity {
+                entity_type: string("type")?,
+                id: serde_json::from_value(field("id").unwrap_or_default())
+                    .map_err(|error| invalid(&error))?,
+                name: match field("name") {
+                    // Names in SNBT are json text
+                    Some(Value::String(name)) if is_legacy => Some(Box::new(
+                        serde_json::from_str(&name).unwrap_or(Chat::Text(name)),
+                    )),
+                    Some(name) => Some(Box::new(
+                        serde_json::from_value(name).map_err(|error| invalid(&error))?,
+                    )),
+                    None => None,
+                },
+            }),
+        })
+    }
+}
+
+/// Append the text of all literal components as is, without interpreting formatting codes
+fn raw_text(chat: &Chat, text: &mut String) {
+    let component_text = |component: &ChatComponent, text: &mut String| {
+        if let TextContent::Literal { text: literal } = &component.content {
+            text.push_str(literal);
+        }
+        for extra in &component.extra {
+            raw_text(extra, text);
+        }
+    };
+    match chat {
+        Chat::Text(literal) | Chat::Legacy(literal) => text.push_str(literal),
+        Chat::Component(component) => component_text(component, text),
+        Chat::Components(components) => {
+            for component in components {
+                component_text(component, text);
+            }
+        }
+    }
+}
+
+impl Serialize for HoverEvent {
+    /// Uses the modern format. See [HoverEvent::to_json_value] for others.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_json_value(HoverFormat::Contents)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for HoverEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct RawHoverEvent {
+            action: HoverAction,
+            contents: Option<Value>,
+            value: Option<Value>,
+            #[serde(flatten)]
+            fields: serde_json::Map<String, Value>,
+        }
+
+        let raw = RawHoverEvent::deserialize(deserializer)?;
+        let (contents, is_legacy) = match (raw.contents, raw.value) {
+            (Some(contents), _) => (contents, false),
+            // Text is the same in all formats
+            (None, Some(value)) if raw.action == HoverAction::ShowText => (value, false),
+            (None, Some(value)) => (value, true),
+            (None, None) if raw.action == HoverAction::ShowText => {
+                return Err(D::Error::missing_field("contents"))
+            }
+            // Inline, with the entity type as id
+            (None, None) => {
+                let mut contents = raw.fields;
+                if raw.action == HoverAction::ShowEntity {
+                    if let Some(entity_type) = contents.remove("id") {
+                        contents.insert("type".to_owned(), entity_type);
+                    }
+                    if let Some(uuid) = contents.remove("uuid") {
+                        contents.insert("id".to_owned(), uuid);
+                    }
+                }
+                (Value::Object(contents), false)
+            }
+        };
+        HoverEvent::from_contents(raw.action, contents, is_legacy).map_err(D::Error::custom)
+    }
+}
+
+/// Rewrite all hover events inside of json text into the given format
+fn rewrite_hover_events(value: &mut Value, format: HoverFormat) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Object(object) => {
+            if format == HoverFormat::Inline {
+                if let Some(click_event) = object.remove("clickEvent") {
+                    let click_event = serde_json::from_value::<ClickEvent>(click_event)?;
+                    object.insert("click_event".to_owned(), click_event.to_inline_json_value());
+                }
+                if let Some(hover_event) = object.remove("hoverEvent") {
+                    object.insert("hover_event".to_owned(), hover_event);
+                }
+            }
+            let key = match format {
+                HoverFormat::Inline => "hover_event",
+                _ => "hoverEvent",
+            };
+            if let Some(hover_event) = object.get_mut(key) {
+                *hover_event = serde_json::from_value::<HoverEvent>(hover_event.take())?
+                    .to_json_value(format)?;
+            }
+            for value in object.values_mut() {
+                rewrite_hover_events(value, format)?;
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                rewrite_hover_events(value, format)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+impl Chat {
+    /// Serialize to json, writing hover events in the format of the targeted version
+    pub fn to_json_value_with(&self, format: HoverFormat) -> Result<Value, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        if format != HoverFormat::Contents {
+            rewrite_hover_events(&mut value, format)?;
+        }
+        Ok(value)
+    }
+}
+
+impl ChatComponent {
+    /// Serialize to json, writing hover events in the format of the targeted version
+    pub fn to_json_value_with(&self, format: HoverFormat) -> Result<Value, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        if format != HoverFormat::Contents {
+            rewrite_hover_events(&mut value, format)?;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid() {
+        let uuid = Uuid::parse("f84c6a79-0a4e-45e0-879b-cd49ebd4c4e2").unwrap();
+        assert_eq!(uuid.to_string(), "f84c6a79-0a4e-45e0-879b-cd49ebd4c4e2");
+        assert_eq!(Uuid::from_int_array(uuid.to_int_array()), uuid);
+        assert_eq!(
+            serde_json::from_value::<Uuid>(json!([-129209735, 172901856, -2019832503, -338377502]))
+                .unwrap(),
+            uuid
+        );
+        assert_eq!(Uuid::parse("f84c6a790a4e45e0879bcd49ebd4c4e2"), Some(uuid));
+        assert_eq!(Uuid::parse("xyz"), None);
+    }
+
+    #[test]
+    fn test_legacy_and_modern() {
+        let item = HoverEvent::ShowItem(HoverItem {
+            id: "minecraft:diamond_sword".to_owned(),
+            count: 2,
+            components: None,
+            tag: Some("{Damage:5}".to_owned()),
+        });
+        let legacy = json!({
+            "action": "show_item",
+            "value": "{Count:2b,id:\"minecraft:diamond_sword\",tag:{Damage:5}}"
+        });
+        let modern = json!({
+            "action": "show_item",
+            "contents": { "id": "minecraft:diamond_sword", "count": 2, "tag": "{Damage:5}" }
+        });
+        assert_eq!(
+            serde_json::from_value::<HoverEvent>(legacy.clone()).unwrap(),
+            item
+        );
+        assert_eq!(
+            serde_json::from_value::<HoverEvent>(modern.clone()).unwrap(),
+            item
+        );
+        assert_eq!(item.to_json_value(HoverFormat::Value).unwrap(), legacy);
+        assert_eq!(serde_json::to_value(&item).unwrap(), modern);
+
+        // Formatting codes in the SNBT are kept and counts are checked
+        let legacy = json!({
+            "action": "show_item",
+            "value": { "text": "{id:\"minecraft:stone\",Count:1b,tag:{display:{Name:'\"§aHi\"'}}}" }
+        });
+        let HoverEvent::ShowItem(item) = serde_json::from_value(legacy).unwrap() else {
+            panic!("Expected item");
+        };
+        assert_eq!(item.tag.as_deref(), Some("{display:{Name:'\"§aHi\"'}}"));
+        assert!(serde_json::from_value::<HoverEvent>(json!({
+            "action": "show_item",
+            "contents": { "id": "minecraft:stone", "count": 1u64 << 40 }
+        }))
+        .is_err());
+        // Items that don't fit the old format are errors instead of getting truncated
+        for item in [
+            HoverItem {
+                count: 300,
+                ..item.clone()
+            },
+            HoverItem {
+                tag: Some("{display:".to_owned()),
+                ..item.clone()
+            },
+            HoverItem {
+                components: Some(json!({ "minecraft:damage": 5 })),
+                tag: None,
+                ..item
+            },
+        ] {
+            assert!(HoverEvent::ShowItem(item)
+                .to_json_value(HoverFormat::Value)
+                .is_err());
+        }
+
+        let entity = HoverEvent::ShowEntity(HoverEntity {
+            entity_type: "minecraft:pig".to_owned(),
+            id: Uuid(1),
+            name: Some(Box::new(Chat::from_legacy("§dPiggy"))),
+        });
+        let legacy = json!({
+            "action": "show_entity",
+            "value": { "text": "{id:\"00000000-0000-0000-0000-000000000001\",name:'{\"text\":\"Piggy\",\"color\":\"light_purple\"}',type:\"minecraft:pig\"}" }
+        });
+        assert_eq!(
+            serde_json::from_value::<HoverEvent>(legacy).unwrap(),
+            entity
+        );
+
+        let chat = Chat::Component(crate::chat::ChatComponent {
+            hover_event: Some(entity),
+            ..Default::default()
+        });
+        let value = chat.to_json_value_with(HoverFormat::Value).unwrap();
+        assert_eq!(serde_json::from_value::<Chat>(value).unwrap(), chat);
+    }
+
+    #[test]
+    fn test_inline() {
+        let chat: Chat = serde_json::from_value(json!({
+            "text": "a",
+            "click_event": { "action": "change_page", "page": 3 },
+            "hover_event": {
+                "action": "show_entity",
+                "id": "minecraft:pig",
+                "uuid": [0, 0, 0, 1],
+                "name": "Piggy"
+            },
+            "extra": [{
+                "text": "b",
+                "hover_event": { "action": "show_item", "id": "minecraft:stone", "count": 2 }
+            }]
+        }))
+        .unwrap();
+        let Chat::Component(component) = &chat else {
+            panic!("Expected component");
+        };
+        assert_eq!(component.click_event.as_ref().unwrap().value, "3");
+        assert_eq!(
+            component.hover_event,
+            Some(HoverEvent::ShowEntity(HoverEntity {
+                entity_type: "minecraft:pig".to_owned(),
+                id: Uuid(1),
+                name: Some(Box::new(Chat::Text("Piggy".to_owned()))),
+            }))
+        );
+
+        let value = chat.to_json_value_with(HoverFormat::Inline).unwrap();
+        assert_eq!(
+            value["click_event"],
+            json!({ "action": "change_page", "page": 3 })
+        );
+        assert_eq!(
+            value["extra"][0]["hover_event"],
+            json!({ "action": "show_item", "id": "minecraft:stone", "count": 2 })
+        );
+        assert_eq!(serde_json::from_value::<Chat>(value).unwrap(), chat);
+    }
+}
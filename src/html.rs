@@ -0,0 +1,501 @@
+use crate::{
+    chat::{Chat, ChatComponent, ClickAction, ClickEvent, TextContent, TextFormatter},
+    error::HtmlError,
+    formatting::{ChatColor, ChatFormat},
+    hover::HoverEvent,
+    style::{TextRun, FORMATS},
+    translator::TranslationProvider,
+};
+
+/// How colors and decorations get applied to html elements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum HtmlStyles {
+    /// `style` attributes, so no stylesheet is needed
+    #[default]
+    Inline,
+    /// Classes like `mc-red` and `mc-bold` to be styled by your own stylesheet.
+    /// Hex colors still use a `style` attribute.
+    Classes,
+}
+
+/// Escape text for use in html content and quoted attribute values
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for chr in text.chars() {
+        match chr {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            chr => escaped.push(chr),
+        }
+    }
+    escaped
+}
+
+fn class_name(color: ChatColor) -> String {
+    format!("mc-{}", color.to_string().replace('_', "-"))
+}
+
+/// Render runs as html, wrapping every styled run in a `<span>` (or `<a>` for links)
+pub(crate) fn runs_to_html(
+    runs: &[TextRun],
+    translator: &dyn TranslationProvider,
+    styles: HtmlStyles,
+) -> String {
+    let mut output = String::new();
+    for run in runs {
+        let style = &run.style;
+        let mut classes = Vec::new();
+        let mut css = Vec::new();
+        let mut attributes = Vec::new();
+
+        match (style.color, styles) {
+            (None | Some(ChatColor::Reset), _) => {}
+            (Some(color @ ChatColor::Hex(_)), _) | (Some(color), HtmlStyles::Inline) => {
+                let rgb = color.rgb().unwrap();
+                css.push(format!("color:#{:02X}{:02X}{:02X}", rgb[0], rgb[1], rgb[2]));
+            }
+            (Some(color), HtmlStyles::Classes) => classes.push(class_name(color)),
+        }
+        match styles {
+            HtmlStyles::Inline => {
+                if style.bold {
+                    css.push("font-weight:bold".to_owned());
+                }
+                if style.italic {
+                    css.push("font-style:italic".to_owned());
+                }
+                let decorations = [
+                    (style.underlined, "underline"),
+                    (style.strikethrough, "line-through"),
+                ]
+                .into_iter()
+                .filter_map(|(active, decoration)| active.then_some(decoration))
+                .collect::<Vec<_>>();
+                if !decorations.is_empty() {
+                    css.push(format!("text-decoration:{}", decorations.join(" ")));
+                }
+            }
+            HtmlStyles::Classes => {
+                for format in FORMATS {
+                    let class = match format {
+                        ChatFormat::Bold => "mc-bold",
+                        ChatFormat::Italic => "mc-italic",
+                        ChatFormat::Underlined => "mc-underlined",
+                        ChatFormat::Strikethrough => "mc-strikethrough",
+                        ChatFormat::Obfuscated => continue,
+                    };
+                    if style.has_format(format) {
+                        classes.push(class.to_owned());
+                    }
+                }
+            }
+        }
+        // Always a class, so the scrambling can be added with a script
+        if style.obfuscated {
+            classes.push("mc-obfuscated".to_owned());
+        }
+
+        if let Some(HoverEvent::ShowText(text)) = &style.hover_event {
+            attributes.push(format!(
+                "title=\"{}\"",
+                escape_html(&text.to_plain_string(translator))
+            ));
+        }
+        if let Some(insertion) = &style.insertion {
+            attributes.push(format!("data-insertion=\"{}\"", escape_html(insertion)));
+        }
+        let mut tag = "span";
+        if let Some(click_event) = &style.click_event {
+            let value = escape_html(&click_event.value);
+            match click_event.action {
+                ClickAction::OpenUrl if is_web_url(&click_event.value) => {
+                    tag = "a";
+                    attributes.push(format!("href=\"{value}\""));
+                }
+                ClickAction::OpenUrl => attributes.push(format!("data-url=\"{value}\"")),
+                ClickAction::OpenFile => attributes.push(format!("data-file=\"{value}\"")),
+                ClickAction::RunCommand => attributes.push(format!("data-run-command=\"{value}\"")),
+                ClickAction::SuggestCommand => {
+                    attributes.push(format!("data-suggest-command=\"{value}\""))
+                }
+                ClickAction::ChangePage => attributes.push(format!("data-page=\"{value}\"")),
+                ClickAction::CopyToClipboard => attributes.push(format!("data-copy=\"{value}\"")),
+            }
+        }
+
+        if !classes.is_empty() {
+            attributes.insert(0, format!("class=\"{}\"", classes.join(" ")));
+        }
+        if !css.is_empty() {
+            attributes.insert(0, format!("style=\"{}\"", css.join(";")));
+        }
+        let text = escape_html(&run.text).replace('\n', "<br>");
+        if attributes.is_empty() {
+            output.push_str(&text);
+        } else {
+            output.push_str(&format!("<{tag} {}>{text}</{tag}>", attributes.join(" ")));
+        }
+    }
+    output
+}
+
+/// What to do with tags the html parser doesn't support
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum UnsupportedHtml {
+    /// Drop the tag but keep its text (contents of `<script>` and `<style>` are dropped as well)
+    #[default]
+    Strip,
+    /// Fail parsing
+    Reject,
+}
+
+struct HtmlTag {
+    /// Lowercase
+    name: String,
+    closing: bool,
+    attributes: Vec<(String, String)>,
+    offset: usize,
+}
+
+impl HtmlTag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Decode the entities html editors commonly produce
+fn unescape_html(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let chr = match entity {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{A0}',
+                _ => match entity.strip_prefix('#') {
+                    Some(hex) if hex.starts_with(['x', 'X']) => {
+                        char::from_u32(u32::from_str_radix(&hex[1..], 16).ok()?)?
+                    }
+                    Some(decimal) => char::from_u32(decimal.parse().ok()?)?,
+                    None => return None,
+                },
+            };
+            Some((chr, end + 1))
+        });
+        match decoded {
+            Some((chr, length)) => {
+                output.push(chr);
+                rest = &rest[length..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Parse the tag at the start of text (starting with "<"). Returns it with its length in bytes.
+fn read_tag(text: &str, offset: usize) -> Option<(HtmlTag, usize)> {
+    let mut chars = text.char_indices().skip(1).peekable();
+    let closing = chars.next_if(|(_, chr)| *chr == '/').is_some();
+    let mut name = String::new();
+    while let Some((_, chr)) = chars.next_if(|(_, chr)| chr.is_ascii_alphanumeric()) {
+        name.push(chr.to_ascii_lowercase());
+    }
+    if name.is_empty() || !name.starts_with(|chr: char| chr.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let mut attributes = Vec::new();
+    loop {
+        while chars
+            .next_if(|(_, chr)| chr.is_whitespace() || *chr == '/')
+            .is_some()
+        {}
+        let (index, chr) = chars.next()?;
+        if chr == '>' {
+            let tag = HtmlTag {
+                name,
+                closing,
+                attributes,
+                offset,
+            };
+            return Some((tag, index + 1));
+        }
+        let mut attribute = chr.to_ascii_lowercase().to_string();
+        while let Some((_, chr)) =
+            chars.next_if(|(_, chr)| !chr.is_whitespace() && !"=>/".contains(*chr))
+        {
+            attribute.push(chr.to_ascii_lowercase());
+        }
+        let mut value = String::new();
+        if chars.next_if(|(_, chr)| *chr == '=').is_some() {
+            match chars.next_if(|(_, chr)| *chr == '"' || *chr == '\'') {
+                Some((_, quote)) => loop {
+                    match chars.next()? {
+                        (_, chr) if chr == quote => break,
+                        (_, chr) => value.push(chr),
+                    }
+                },
+                None => {
+                    while let Some((_, chr)) =
+                        chars.next_if(|(_, chr)| !chr.is_whitespace() && *chr != '>')
+                    {
+                        value.push(chr);
+                    }
+                }
+            }
+        }
+        attributes.push((attribute, unescape_html(&value)));
+    }
+}
+
+/// The color of a css `color` declaration in a style attribute
+fn css_color(style: &str) -> Option<ChatColor> {
+    style.split(';').find_map(|declaration| {
+        let (property, value) = declaration.split_once(':')?;
+        if !property.trim().eq_ignore_ascii_case("color") {
+            return None;
+        }
+        let value = value.trim();
+        let hex = match value.len() {
+            // Short form like #F0A
+            4 => value.chars().skip(1).flat_map(|chr| [chr, chr]).collect(),
+            _ => value.get(1..)?.to_owned(),
+        };
+        if !value.starts_with('#') || hex.len() != 6 {
+            return None;
+        }
+        ChatColor::from_hex_str(&format!("#{hex}")).ok()
+    })
+}
+
+/// Only http(s) urls become links, so something like javascript: urls can't get through
+fn is_web_url(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+/// Build the component a supported tag opens. None for unsupported tags.
+fn tag_component(tag: &HtmlTag) -> Option<ChatComponent> {
+    let mut component = ChatComponent::default();
+    match tag.name.as_str() {
+        "b" | "strong" => component.bold = Some(true),
+        "i" | "em" => component.italic = Some(true),
+        "u" | "ins" => component.underlined = Some(true),
+        "s" | "strike" | "del" => component.strikethrough = Some(true),
+        "a" => {
+            component.click_event =
+                tag.attribute("href")
+                    .filter(|href| is_web_url(href))
+                    .map(|href| ClickEvent {
+                        action: ClickAction::OpenUrl,
+                        value: href.to_owned(),
+                    })
+        }
+        "span" => component.color = tag.attribute("style").and_then(css_color),
+        _ => return None,
+    }
+    Some(component)
+}
+
+fn is_empty(component: &ChatComponent) -> bool {
+    component.content == TextContent::default() && component.extra.is_empty()
+}
+
+fn push_child(parent: &mut ChatComponent, child: ChatComponent) {
+    if !is_empty(&child) {
+        parent.extra.push(Chat::Component(child));
+    }
+}
+
+impl Chat {
+    /// Parse the html subset rich text editors produce: `<b>`, `<i>`, `<u>`, `<s>`,
+    /// `<a href>`, `<span style="color:#...">` and `<br>`. Unclosed tags get closed automatically
+    /// and closing tags without an opening one are ignored.
+    pub fn from_html(html: &str, unsupported: UnsupportedHtml) -> Result<Chat, HtmlError> {
+        // Open tags and their components, the root not having a tag
+        let mut stack = vec![(String::new(), ChatComponent::default())];
+        let mut text = String::new();
+        let mut offset = 0;
+
+        let flush_text = |text: &mut String, stack: &mut Vec<(String, ChatComponent)>| {
+            if !text.is_empty() {
+                let component = &mut stack.last_mut().unwrap().1;
+                component.extra.push(Chat::Component(ChatComponent {
+                    content: TextContent::new_literal(&unescape_html(text)),
+                    ..Default::default()
+                }));
+                text.clear();
+            }
+        };
+
+        while offset < html.len() {
+            let rest = &html[offset..];
+            if let Some(comment) = rest.strip_prefix("<!") {
+                // Comments and doctype
+                let end = if comment.starts_with("--") {
+                    comment.find("-->").map(|end| end + 3)
+                } else {
+                    comment.find('>').map(|end| end + 1)
+                };
+                offset += 2 + end.unwrap_or(comment.len());
+                continue;
+            }
+            let Some((tag, length)) = rest
+                .starts_with('<')
+                .then(|| read_tag(rest, offset))
+                .flatten()
+            else {
+                let chr = rest.chars().next().unwrap();
+                text.push(chr);
+                offset += chr.len_utf8();
+                continue;
+            };
+            offset += length;
+            flush_text(&mut text, &mut stack);
+
+            if tag.name == "br" {
+                if !tag.closing {
+                    stack
+                        .last_mut()
+                        .unwrap()
+                        .1
+                        .extra
+                        .push(Chat::Component(ChatComponent {
+                            content: TextContent::new_literal("\n"),
+                            ..Default::default()
+                        }));
+                }
+                continue;
+            }
+            if tag.closing {
+                if let Some(index) = stack.iter().rposition(|(name, _)| *name == tag.name) {
+                    while stack.len() > index {
+                        let (_, component) = stack.pop().unwrap();
+                        push_child(&mut stack.last_mut().unwrap().1, component);
+                    }
+                }
+                continue;
+            }
+            match (tag_component(&tag), unsupported) {
+                (Some(component), _) => stack.push((tag.name, component)),
+                (None, UnsupportedHtml::Reject) => {
+                    return Err(HtmlError::UnsupportedTag {
+                        tag: tag.name,
+                        offset: tag.offset,
+                    })
+                }
+                (None, UnsupportedHtml::Strip)
+                    if matches!(tag.name.as_str(), "script" | "style") =>
+                {
+                    let closing = format!("</{}", tag.name);
+                    offset += html[offset..]
+                        .to_ascii_lowercase()
+                        .find(&closing)
+                        .and_then(|start| {
+                            html[offset + start..].find('>').map(|end| start + end + 1)
+                        })
+                        .unwrap_or(html.len() - offset);
+                }
+                (None, UnsupportedHtml::Strip) => {}
+            }
+        }
+        flush_text(&mut text, &mut stack);
+        while stack.len() > 1 {
+            let (_, component) = stack.pop().unwrap();
+            push_child(&mut stack.last_mut().unwrap().1, component);
+        }
+
+        let (_, mut root) = stack.pop().unwrap();
+        if root.extra.len() == 1 {
+            if let Chat::Component(component) = root.extra.remove(0) {
+                return Ok(Chat::Component(component));
+            }
+        }
+        Ok(Chat::Component(root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translator::Translator;
+
+    #[test]
+    fn test_html() {
+        let chat = Chat::from_minimessage(
+            "<red><b>Hi</b> <#FF0080><u><st>you</st></u></#FF0080> <obf>?</obf></red>\n<click:open_url:'https://example.com/?a=1&b=2'><hover:show_text:'<gold>Open \"it\"'>Link</hover></click> <click:run_command:'/say <3'>Run</click> <click:open_url:'javascript:alert(1)'>x</click>",
+        );
+        let translator = Translator::default();
+        assert_eq!(
+            chat.to_html_string(&translator),
+            concat!(
+                "<span style=\"color:#FF5555;font-weight:bold\">Hi</span>",
+                "<span style=\"color:#FF5555\"> </span>",
+                "<span style=\"color:#FF0080;text-decoration:underline line-through\">you</span>",
+                "<span style=\"color:#FF5555\"> </span>",
+                "<span style=\"color:#FF5555\" class=\"mc-obfuscated\">?</span>",
+                "<br>",
+                "<a title=\"Open &quot;it&quot;\" href=\"https://example.com/?a=1&amp;b=2\">Link</a> ",
+                "<span data-run-command=\"/say &lt;3\">Run</span> ",
+                "<span data-url=\"javascript:alert(1)\">x</span>"
+            )
+        );
+        assert_eq!(
+            Chat::from_legacy("§c§lHi§x§1§2§3§4§5§6§nyou")
+                .to_html_string_with(&translator, HtmlStyles::Classes),
+            "<span class=\"mc-red mc-bold\">Hi</span><span style=\"color:#123456\" class=\"mc-underlined\">you</span>"
+        );
+    }
+
+    #[test]
+    fn test_parse_html() {
+        let chat = Chat::from_html(
+            "<p>Hello <b>bold <i>both</b> italic?</i><br><a href=\"https://example.com/?a=1&amp;b=2\">link</a> <span style=\"font-size: 2em; color: #ff0080\">pink &lt;3</span><script>alert(1)</script><!-- x -->",
+            UnsupportedHtml::Strip,
+        )
+        .unwrap();
+        assert_eq!(
+            chat.to_minimessage(),
+            "Hello <bold>bold <italic>both</italic></bold> italic?\n<click:open_url:'https://example.com/?a=1&b=2'>link</click> <#ff0080>pink \\<3"
+        );
+        assert_eq!(
+            Chat::from_html("<b>a</b><p>b</p>", UnsupportedHtml::Reject),
+            Err(HtmlError::UnsupportedTag {
+                tag: "p".to_owned(),
+                offset: 8
+            })
+        );
+        assert_eq!(
+            Chat::from_html("1 < 2 && <u>x", UnsupportedHtml::Reject)
+                .unwrap()
+                .to_minimessage(),
+            "1 \\< 2 && <underlined>x"
+        );
+        assert_eq!(
+            Chat::from_html(
+                "<a href=\"javascript:alert(1)\">x</a>",
+                UnsupportedHtml::Reject
+            )
+            .unwrap()
+            .to_minimessage(),
+            "x"
+        );
+    }
+}
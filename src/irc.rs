@@ -0,0 +1,256 @@
+use crate::{
+    chat::{Chat, ChatComponent, TextContent},
+    color::{self, ColorDistance},
+    formatting::{ChatColor, ChatFormat},
+    style::{Style, TextRun},
+};
+
+const BOLD: char = '\x02';
+const COLOR: char = '\x03';
+const HEX_COLOR: char = '\x04';
+const RESET: char = '\x0F';
+const MONOSPACE: char = '\x11';
+const REVERSE: char = '\x16';
+const ITALIC: char = '\x1D';
+const STRIKETHROUGH: char = '\x1E';
+const UNDERLINE: char = '\x1F';
+
+/// Formats that have a mIRC code (obfuscated has none)
+const IRC_FORMATS: [(ChatFormat, char); 4] = [
+    (ChatFormat::Bold, BOLD),
+    (ChatFormat::Italic, ITALIC),
+    (ChatFormat::Underlined, UNDERLINE),
+    (ChatFormat::Strikethrough, STRIKETHROUGH),
+];
+
+/// Options for converting between mIRC formatting codes and chat
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrcFormatting {
+    /// The color for each mIRC color number (index). Numbers without an entry get the default color.
+    pub colors: Vec<ChatColor>,
+    /// Render hex colors with `\x04RRGGBB` instead of the closest color of the table
+    pub hex_colors: bool,
+}
+
+impl Default for IrcFormatting {
+    /// The 16 standard mIRC colors mapped to the named colors
+    fn default() -> Self {
+        Self {
+            colors: vec![
+                ChatColor::White,
+                ChatColor::Black,
+                ChatColor::DarkBlue,
+                ChatColor::DarkGreen,
+                ChatColor::Red,
+                ChatColor::DarkRed,
+                ChatColor::DarkPurple,
+                ChatColor::Gold,
+                ChatColor::Yellow,
+                ChatColor::Green,
+                ChatColor::DarkAqua,
+                ChatColor::Aqua,
+                ChatColor::Blue,
+                ChatColor::LightPurple,
+                ChatColor::DarkGray,
+                ChatColor::Gray,
+            ],
+            hex_colors: false,
+        }
+    }
+}
+
+impl IrcFormatting {
+    /// The code switching to the color
+    fn color_code(&self, color: ChatColor) -> Option<String> {
+        if let (ChatColor::Hex(rgb), true) = (color, self.hex_colors) {
+            return Some(format!(
+                "{HEX_COLOR}{:02X}{:02X}{:02X}",
+                rgb[0], rgb[1], rgb[2]
+            ));
+        }
+        let number = match self.colors.iter().position(|entry| *entry == color) {
+            Some(number) => number,
+            None => {
+                let rgb = color.rgb()?;
+                let palette = self
+                    .colors
+                    .iter()
+                    .filter_map(|entry| entry.rgb())
+                    .collect::<Vec<_>>();
+                if palette.is_empty() {
+                    return None;
+                }
+                let nearest = palette[color::nearest_index(rgb, &palette, ColorDistance::OkLab)];
+                self.colors
+                    .iter()
+                    .position(|entry| entry.rgb() == Some(nearest))?
+            }
+        };
+        // Always two digits, so following digits don't become part of the number
+        Some(format!("{COLOR}{number:02}"))
+    }
+}
+
+/// Render runs with mIRC formatting codes, only writing what changes between runs
+pub(crate) fn runs_to_irc(runs: &[TextRun], options: &IrcFormatting) -> String {
+    let mut output = String::new();
+    let mut current = Style::default();
+    let mut current_color = None;
+    for run in runs {
+        let color = run.style.color.and_then(|color| options.color_code(color));
+        let mut color_code_last = false;
+        if color != current_color {
+            match &color {
+                Some(code) => {
+                    output.push_str(code);
+                    color_code_last = true;
+                }
+                None => {
+                    // A lone color code would take following digits as color
+                    output.push(RESET);
+                    current = Style::default();
+                }
+            }
+            current_color = color;
+        }
+        for (format, code) in IRC_FORMATS {
+            if current.has_format(format) != run.style.has_format(format) {
+                output.push(code);
+                color_code_last = false;
+            }
+        }
+        current = run.style.clone();
+        // A comma after a color code would start the background color
+        if color_code_last && run.text.starts_with(',') {
+            output.push_str("\x02\x02");
+        }
+        output.push_str(&run.text);
+    }
+    output
+}
+
+/// Read up to max_length chars matching the predicate
+fn read_digits(text: &str, max_length: usize, predicate: impl Fn(&char) -> bool) -> &str {
+    let length = text
+        .chars()
+        .take(max_length)
+        .take_while(|chr| predicate(chr))
+        .count();
+    &text[..length]
+}
+
+impl Chat {
+    /// Parse text with mIRC formatting codes. Background colors, reverse and monospace are ignored.
+    pub fn from_irc(irc_text: &str, options: &IrcFormatting) -> Chat {
+        let mut components = Vec::new();
+        let mut style = Style::default();
+        let mut text = String::new();
+        let mut rest = irc_text;
+        while let Some(chr) = rest.chars().next() {
+            rest = &rest[chr.len_utf8()..];
+            let mut new_style = style.clone();
+            match chr {
+                RESET => new_style = Style::default(),
+                COLOR | HEX_COLOR => {
+                    let (digits, is_digit): (usize, fn(&char) -> bool) = match chr {
+                        COLOR => (2, char::is_ascii_digit),
+                        _ => (6, char::is_ascii_hexdigit),
+                    };
+                    let foreground = read_digits(rest, digits, is_digit);
+                    rest = &rest[foreground.len()..];
+                    if !foreground.is_empty() && rest.starts_with(',') {
+                        let background = read_digits(&rest[1..], digits, is_digit);
+                        if !background.is_empty() {
+                            rest = &rest[1 + background.len()..];
+                        }
+                    }
+                    new_style.color = match chr {
+                        _ if foreground.is_empty() => None,
+                        COLOR => foreground
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|number| options.colors.get(number).copied()),
+                        _ if foreground.len() == 6 => {
+                            ChatColor::from_hex_str(&format!("#{foreground}")).ok()
+                        }
+                        _ => None,
+                    };
+                }
+                MONOSPACE | REVERSE => continue,
+                _ => match IRC_FORMATS.iter().find(|(_, code)| *code == chr) {
+                    Some((format, _)) => new_style.set_format(*format, !style.has_format(*format)),
+                    None => {
+                        text.push(chr);
+                        continue;
+                    }
+                },
+            }
+            if !text.is_empty() {
+                components.push(style_component(&text, &style));
+                text.clear();
+            }
+            style = new_style;
+        }
+        if !text.is_empty() {
+            components.push(style_component(&text, &style));
+        }
+
+        if components.len() == 1 {
+            return Chat::Component(components.remove(0));
+        }
+        Chat::Component(ChatComponent {
+            extra: components.into_iter().map(Chat::Component).collect(),
+            ..Default::default()
+        })
+    }
+}
+
+fn style_component(text: &str, style: &Style) -> ChatComponent {
+    ChatComponent {
+        content: TextContent::new_literal(text),
+        color: style.color,
+        bold: style.bold.then_some(true),
+        italic: style.italic.then_some(true),
+        underlined: style.underlined.then_some(true),
+        strikethrough: style.strikethrough.then_some(true),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chat::TextFormatter, translator::Translator};
+
+    #[test]
+    fn test_to_irc() {
+        let chat =
+            Chat::from_minimessage("<red>1<b>2</b></red>3<#FF0080><i>4</#FF0080><gold><i>,5");
+        let translator = Translator::default();
+        assert_eq!(
+            chat.to_irc_string(&translator),
+            "\x03041\x022\x0F3\x0304\x1D4\x0307\x02\x02,5"
+        );
+        let options = IrcFormatting {
+            hex_colors: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            chat.to_irc_string_with(&translator, &options),
+            "\x03041\x022\x0F3\x04FF0080\x1D4\x0307\x02\x02,5"
+        );
+    }
+
+    #[test]
+    fn test_from_irc() {
+        let options = IrcFormatting::default();
+        let chat = Chat::from_irc(
+            "\x02bold\x02 \x034,12red\x03 \x1Fund\x0399,01x\x04ff0080hex\x0Fplain\x16",
+            &options,
+        );
+        assert_eq!(
+            chat.to_minimessage(),
+            "<bold>bold</bold> <red>red</red> <underlined>und</underlined><underlined>x</underlined><#ff0080><underlined>hex</underlined></#ff0080>plain"
+        );
+    }
+}
@@ -0,0 +1,655 @@
+use std::ops::Range;
+
+use crate::{
+    chat::{Chat, ChatComponent, TextContent},
+    color::ColorDistance,
+    error::LegacyError,
+    formatting::{ChatColor, ChatFormat},
+    style::{Style, TextRun, FORMATS},
+};
+
+/// Ways hex colors get written in legacy text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LegacyHexFormat {
+    /// BungeeCord/Spigot: `§x§R§R§G§G§B§B` (using the prefix of the dialect)
+    Bungee,
+    /// Essentials/CMI: `&#RRGGBB` (using the prefix of the dialect)
+    Hash,
+    /// CMI: `{#RRGGBB}`
+    Braces,
+}
+
+/// How a literal prefix character gets written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LegacyEscape {
+    /// Writing the prefix twice (`&&`)
+    Doubled,
+    /// A backslash before the prefix (`\&`)
+    Backslash,
+}
+
+/// The syntax of legacy formatted text
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LegacyDialect {
+    /// The character starting a code (like § or &)
+    pub prefix: char,
+    /// Accepted hex color encodings. The first one is used when rendering.
+    pub hex_formats: Vec<LegacyHexFormat>,
+    pub escape: Option<LegacyEscape>,
+    /// Drop the prefix and following character of unknown codes (like the client does with §).
+    /// Otherwise they are kept as text, so "Tom & Jerry" stays intact.
+    pub drop_unknown_codes: bool,
+}
+
+impl Default for LegacyDialect {
+    fn default() -> Self {
+        Self::section()
+    }
+}
+
+impl LegacyDialect {
+    /// What the client and servers use: `§` codes and BungeeCord hex colors
+    pub fn section() -> Self {
+        Self {
+            prefix: '§',
+            hex_formats: vec![LegacyHexFormat::Bungee],
+            escape: None,
+            drop_unknown_codes: true,
+        }
+    }
+
+    /// `&` codes like in most plugin configs (Essentials style), with `&#RRGGBB`
+    /// and `&x&R&R&G&G&B&B` hex colors and `&&` for a literal `&`
+    pub fn ampersand() -> Self {
+        Self {
+            prefix: '&',
+            hex_formats: vec![LegacyHexFormat::Hash, LegacyHexFormat::Bungee],
+            escape: Some(LegacyEscape::Doubled),
+            drop_unknown_codes: false,
+        }
+    }
+
+    /// `&` codes like CMI, with `{#RRGGBB}` hex colors additionally to the [LegacyDialect::ampersand] ones
+    pub fn cmi() -> Self {
+        Self {
+            hex_formats: vec![
+                LegacyHexFormat::Braces,
+                LegacyHexFormat::Hash,
+                LegacyHexFormat::Bungee,
+            ],
+            ..Self::ampersand()
+        }
+    }
+
+    /// Split legacy text into text and codes
+    pub fn tokenize<'a>(&'a self, text: &'a str) -> LegacyLexer<'a> {
+        LegacyLexer {
+            dialect: self,
+            text,
+            position: 0,
+        }
+    }
+
+    /// Find all unknown codes and prefixes without a code
+    pub fn diagnostics(&self, text: &str) -> Vec<LegacyError> {
+        self.tokenize(text)
+            .filter_map(|token| match token.kind {
+                LegacyTokenKind::Unknown(code) => Some(LegacyError::UnknownCode {
+                    code,
+                    offset: token.span.start,
+                }),
+                LegacyTokenKind::Dangling => Some(LegacyError::DanglingPrefix {
+                    offset: token.span.start,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Read the code at the start of text. Returns it with its length in bytes,
+    /// or none if text doesn't start with a code.
+    fn read_code(&self, text: &str) -> Option<(LegacyTokenKind, usize)> {
+        let prefix_len = self.prefix.len_utf8();
+        // Length of an escaped chr at the start of text
+        let escaped = |chr: char| match self.escape {
+            Some(LegacyEscape::Doubled)
+                if text
+                    .strip_prefix(chr)
+                    .is_some_and(|rest| rest.starts_with(chr)) =>
+            {
+                Some(2 * chr.len_utf8())
+            }
+            Some(LegacyEscape::Backslash)
+                if text.starts_with('\\') && text[1..].starts_with(chr) =>
+            {
+                Some(1 + chr.len_utf8())
+            }
+            _ => None,
+        };
+        if let Some(length) = escaped(self.prefix) {
+            return Some((LegacyTokenKind::Escaped, length));
+        }
+        let has_braces = self.hex_formats.contains(&LegacyHexFormat::Braces);
+        if let Some(length) =
+            escaped('{').filter(|length| has_braces && text[*length..].starts_with('#'))
+        {
+            return Some((LegacyTokenKind::Escaped, length));
+        }
+        if has_braces && text.starts_with("{#") {
+            if let Some(color) = text.get(1..8).and_then(parse_hex_digits) {
+                if text[8..].starts_with('}') {
+                    return Some((LegacyTokenKind::Color(color), 9));
+                }
+            }
+        }
+
+        let after_prefix = text.strip_prefix(self.prefix)?;
+        let Some(code) = after_prefix.chars().next() else {
+            return Some((LegacyTokenKind::Dangling, prefix_len));
+        };
+        let after_code = &after_prefix[code.len_utf8()..];
+        let code_len = prefix_len + code.len_utf8();
+        if matches!(code, 'x' | 'X') && self.hex_formats.contains(&LegacyHexFormat::Bungee) {
+            if let Some((color, length)) = self.read_bungee_digits(after_code) {
+                return Some((LegacyTokenKind::Color(color), code_len + length));
+            }
+        }
+        if code == '#' && self.hex_formats.contains(&LegacyHexFormat::Hash) {
+            if let Some(color) = after_code
+                .get(..6)
+                .and_then(|digits| parse_hex_digits(&format!("#{digits}")))
+            {
+                return Some((LegacyTokenKind::Color(color), code_len + 6));
+            }
+        }
+        let code = code.to_ascii_lowercase();
+        if let Ok(format) = ChatFormat::from_format_code_char(code) {
+            return Some((LegacyTokenKind::Format(format), code_len));
+        }
+        Some(match ChatColor::from_color_code_char(code) {
+            Ok(ChatColor::Reset) => (LegacyTokenKind::Reset, code_len),
+            Ok(color) => (LegacyTokenKind::Color(color), code_len),
+            Err(_) => (LegacyTokenKind::Unknown(code), code_len),
+        })
+    }
+
+    /// Parse the six prefixed hex digits following a BungeeCord hex color start (`§x`).
+    /// Returns the color and the length of the digits in bytes.
+    fn read_bungee_digits(&self, after_x: &str) -> Option<(ChatColor, usize)> {
+        let mut chars = after_x.chars();
+        let mut hex = String::with_capacity(7);
+        hex.push('#');
+        for _ in 0..6 {
+            match (chars.next(), chars.next()) {
+                (Some(prefix), Some(digit)) if prefix == self.prefix => hex.push(digit),
+                _ => return None,
+            }
+        }
+        Some((parse_hex_digits(&hex)?, 6 * (self.prefix.len_utf8() + 1)))
+    }
+
+    /// Write literal text, escaping the prefix (and `{#` when using braces hex colors) if possible
+    fn push_text(&self, output: &mut String, text: &str) {
+        let has_braces = self.hex_formats.contains(&LegacyHexFormat::Braces);
+        let mut chars = text.chars().peekable();
+        while let Some(chr) = chars.next() {
+            if chr == self.prefix || (has_braces && chr == '{' && chars.peek() == Some(&'#')) {
+                match self.escape {
+                    Some(LegacyEscape::Doubled) => output.push(chr),
+                    Some(LegacyEscape::Backslash) => output.push('\\'),
+                    None => {}
+                }
+            }
+            output.push(chr);
+        }
+    }
+
+    fn code(&self, code: char) -> String {
+        format!("{}{code}", self.prefix)
+    }
+
+    fn hex_code(&self, rgb: [u8; 3]) -> Option<String> {
+        let hex = format!("{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]);
+        Some(match self.hex_formats.first()? {
+            LegacyHexFormat::Bungee => std::iter::once('x')
+                .chain(hex.chars())
+                .map(|chr| self.code(chr))
+                .collect(),
+            LegacyHexFormat::Hash => format!("{}#{hex}", self.prefix),
+            LegacyHexFormat::Braces => format!("{{#{hex}}}"),
+        })
+    }
+}
+
+/// A piece of legacy text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyToken {
+    pub kind: LegacyTokenKind,
+    /// Byte range in the text
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyTokenKind {
+    /// Text without any codes
+    Text,
+    /// Switches the color and clears all formats
+    Color(ChatColor),
+    Format(ChatFormat),
+    /// Clears color and formats
+    Reset,
+    /// A literal prefix (or `{` of something looking like a braces hex color)
+    Escaped,
+    /// Prefix followed by something that isn't a code
+    Unknown(char),
+    /// Prefix at the end of the text
+    Dangling,
+}
+
+/// Iterator over the tokens of legacy text. See [LegacyDialect::tokenize].
+pub struct LegacyLexer<'a> {
+    dialect: &'a LegacyDialect,
+    text: &'a str,
+    position: usize,
+}
+
+impl Iterator for LegacyLexer<'_> {
+    type Item = LegacyToken;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.position;
+        let rest = &self.text[start..];
+        if rest.is_empty() {
+            return None;
+        }
+        if let Some((kind, length)) = self.dialect.read_code(rest) {
+            self.position += length;
+            return Some(LegacyToken {
+                kind,
+                span: start..self.position,
+            });
+        }
+        let text_length = rest
+            .char_indices()
+            .skip(1)
+            .find(|(index, _)| self.dialect.read_code(&rest[*index..]).is_some())
+            .map(|(index, _)| index)
+            .unwrap_or(rest.len());
+        self.position += text_length;
+        Some(LegacyToken {
+            kind: LegacyTokenKind::Text,
+            span: start..self.position,
+        })
+    }
+}
+
+/// Only accepts "#" followed by exactly 6 hex digits (no signs like from_str_radix)
+fn parse_hex_digits(hex: &str) -> Option<ChatColor> {
+    if hex.len() != 7 || !hex[1..].chars().all(|chr| chr.is_ascii_hexdigit()) {
+        return None;
+    }
+    ChatColor::from_hex_str(hex).ok()
+}
+
+/// What to do with hex colors, when rendering to legacy text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LegacyHexColors {
+    /// Render the text with the default color instead
+    Drop,
+    /// Use the closest named color
+    Nearest(ColorDistance),
+    /// Keep the exact color using the first hex format of the dialect, which Spigot
+    /// and BungeeCord understand, but the vanilla client doesn't. If the dialect
+    /// has none, the closest named color is used.
+    Exact,
+}
+
+impl Default for LegacyHexColors {
+    fn default() -> Self {
+        LegacyHexColors::Nearest(ColorDistance::default())
+    }
+}
+
+impl LegacyHexColors {
+    /// The full legacy code (including the prefix) to switch to the color
+    fn color_code(self, color: ChatColor, dialect: &LegacyDialect) -> Option<String> {
+        match (color, self) {
+            (ChatColor::Hex(_), LegacyHexColors::Drop) => None,
+            (ChatColor::Hex(rgb), LegacyHexColors::Exact) => dialect.hex_code(rgb).or_else(|| {
+                LegacyHexColors::Nearest(ColorDistance::default()).color_code(color, dialect)
+            }),
+            (ChatColor::Hex(_), LegacyHexColors::Nearest(metric)) => color
+                .to_named(metric)
+                .into_color_code()
+                .map(|code| dialect.code(code)),
+            _ => color.into_color_code().map(|code| dialect.code(code)),
+        }
+    }
+}
+
+/// Render runs as legacy text, only writing codes where the style changes
+pub(crate) fn runs_to_legacy(
+    runs: &[TextRun],
+    initial_style: &Style,
+    dialect: &LegacyDialect,
+    hex_colors: LegacyHexColors,
+) -> String {
+    let legacy_color = |style: &Style| {
+        style
+            .color
+            .and_then(|color| hex_colors.color_code(color, dialect))
+    };
+
+    let mut output = String::new();
+    let mut current = initial_style.clone();
+    for run in runs {
+        // Colors and resets are the only way to turn off formats
+        let removes_format = FORMATS
+            .iter()
+            .any(|format| current.has_format(*format) && !run.style.has_format(*format));
+        if removes_format || legacy_color(&current) != legacy_color(&run.style) {
+            output.push_str(&legacy_color(&run.style).unwrap_or_else(|| dialect.code('r')));
+            current = Style {
+                color: run.style.color,
+                ..Default::default()
+            };
+        }
+        for format in FORMATS {
+            if run.style.has_format(format) && !current.has_format(format) {
+                output.push_str(&dialect.code(format.into_format_code()));
+                current.set_format(format, true);
+            }
+        }
+        dialect.push_text(&mut output, &run.text);
+    }
+    output
+}
+
+impl Chat {
+    /// Parse legacy text using the given syntax (like `&` codes)
+    pub fn from_legacy_dialect(legacy_text: &str, dialect: &LegacyDialect) -> Self {
+        let mut builder = LegacyTreeBuilder::default();
+        for token in dialect.tokenize(legacy_text) {
+            match token.kind {
+                LegacyTokenKind::Text => builder.push_text(&legacy_text[token.span]),
+                // The escaped char comes last
+                LegacyTokenKind::Escaped => {
+                    let escape = &legacy_text[token.span];
+                    builder.push_text(
+                        &escape[escape.len() - escape.chars().last().map_or(0, char::len_utf8)..],
+                    )
+                }
+                LegacyTokenKind::Unknown(_) | LegacyTokenKind::Dangling => {
+                    if !dialect.drop_unknown_codes {
+                        builder.push_text(&legacy_text[token.span]);
+                    }
+                }
+                LegacyTokenKind::Color(color) => builder.set_color(Some(color)),
+                LegacyTokenKind::Reset => builder.set_color(None),
+                LegacyTokenKind::Format(format) => builder.add_format(format),
+            }
+        }
+        builder.finish()
+    }
+}
+
+/// Builds a tree where colors (and resets) start a new top level component and
+/// formats nest the following text inside the current component
+#[derive(Default)]
+struct LegacyTreeBuilder {
+    /// Finished top level components
+    siblings: Vec<ChatComponent>,
+    /// The current top level component followed by its nested children. Text goes into the last one.
+    path: Vec<ChatComponent>,
+    color: Option<ChatColor>,
+    formats: Vec<ChatFormat>,
+}
+
+impl LegacyTreeBuilder {
+    fn current(&mut self) -> &mut ChatComponent {
+        if self.path.is_empty() {
+            self.path.push(ChatComponent {
+                color: self.color,
+                ..Default::default()
+            });
+        }
+        self.path.last_mut().unwrap()
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if let TextContent::Literal { text: literal } = &mut self.current().content {
+            literal.push_str(text);
+        }
+    }
+
+    fn set_color(&mut self, color: Option<ChatColor>) {
+        if color == self.color && self.formats.is_empty() {
+            return;
+        }
+        self.close();
+        self.color = color;
+        self.formats.clear();
+    }
+
+    fn add_format(&mut self, format: ChatFormat) {
+        if self.formats.contains(&format) {
+            return;
+        }
+        self.formats.push(format);
+        if self.current().content != TextContent::default() {
+            self.path.push(ChatComponent::default());
+        }
+        let current = self.current();
+        match format {
+            ChatFormat::Bold => current.bold = Some(true),
+            ChatFormat::Italic => current.italic = Some(true),
+            ChatFormat::Underlined => current.underlined = Some(true),
+            ChatFormat::Strikethrough => current.strikethrough = Some(true),
+            ChatFormat::Obfuscated => current.obfuscated = Some(true),
+        }
+    }
+
+    /// Finish the current top level component, dropping empty ones
+    fn close(&mut self) {
+        let is_empty = |component: &ChatComponent| {
+            component.content == TextContent::default() && component.extra.is_empty()
+        };
+        while let Some(component) = self.path.pop() {
+            if is_empty(&component) {
+                continue;
+            }
+            match self.path.last_mut() {
+                Some(parent) => parent.extra.push(Chat::Component(component)),
+                None => self.siblings.push(component),
+            }
+        }
+    }
+
+    fn finish(mut self) -> Chat {
+        self.close();
+        // Siblings inherit the style of their parent, so they can't be children of the first one
+        if self.siblings.len() == 1 {
+            return Chat::Component(self.siblings.remove(0));
+        }
+        Chat::Component(ChatComponent {
+            extra: self.siblings.into_iter().map(Chat::Component).collect(),
+            ..Default::default()
+        })
+    }
+}
+
+pub(crate) fn legacy_to_plain(input: &str) -> String {
+    let mut output = String::new();
+    let mut was_paragraph = false;
+    for chr in input.chars() {
+        if was_paragraph {
+            was_paragraph = false;
+        } else {
+            if chr == '§' {
+                was_paragraph = true;
+            } else {
+                output.push(chr);
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat::{Chat, TextFormatter},
+        translator::Translator,
+    };
+
+    #[test]
+    fn test_hex_colors() {
+        let chat = Chat::from_minimessage("<#F04040>a<#F04041>b<#101010>c");
+        let translator = Translator::default();
+        assert_eq!(chat.to_legacy_string(&translator), "§cab§0c");
+        assert_eq!(
+            chat.to_legacy_string_with(
+                &translator,
+                &LegacyDialect::section(),
+                LegacyHexColors::Drop
+            ),
+            "abc"
+        );
+        assert_eq!(
+            chat.to_legacy_string_with(
+                &translator,
+                &LegacyDialect::section(),
+                LegacyHexColors::Exact
+            ),
+            "§x§f§0§4§0§4§0a§x§f§0§4§0§4§1b§x§1§0§1§0§1§0c"
+        );
+    }
+
+    #[test]
+    fn test_bungee_hex() {
+        // Incomplete sequences are read as separate codes
+        let legacy = "§x§f§0§4§0§4§0§lHex§x§1§2§3c";
+        let chat = Chat::from_legacy(legacy);
+        assert_eq!(
+            chat.to_minimessage(),
+            "<#f04040><bold>Hex</bold></#f04040><dark_aqua>c"
+        );
+        assert_eq!(
+            Chat::Legacy(legacy.to_owned()).to_text_runs(&Translator::default())[0]
+                .style
+                .color,
+            Some(ChatColor::Hex([0xF0, 0x40, 0x40]))
+        );
+    }
+
+    #[test]
+    fn test_dialects() {
+        let translator = Translator::default();
+        let dialect = LegacyDialect::ampersand();
+        let chat = Chat::from_legacy_dialect("&6&lHello &#ff00aaworld &&b Tom & Jerry&", &dialect);
+        assert_eq!(
+            chat.to_minimessage(),
+            "<gold><bold>Hello </bold></gold><#ff00aa>world &b Tom & Jerry&"
+        );
+        assert_eq!(
+            chat.to_legacy_string_with(&translator, &dialect, LegacyHexColors::Exact),
+            "&6&lHello &#ff00aaworld &&b Tom && Jerry&&"
+        );
+
+        let chat = Chat::from_legacy_dialect("{#00ff00}a&x&1&2&3&4&5&6b", &LegacyDialect::cmi());
+        assert_eq!(chat.to_minimessage(), "<#00ff00>a</#00ff00><#123456>b");
+
+        // Literal text looking like a braces hex color survives the round trip
+        let dialect = LegacyDialect::cmi();
+        let chat = Chat::Text("{#ff0000}hi {{#00ff00} &".to_owned());
+        let legacy = chat.to_legacy_string_with(&translator, &dialect, LegacyHexColors::Exact);
+        assert_eq!(legacy, "{{#ff0000}hi {{{#00ff00} &&");
+        assert_eq!(
+            Chat::from_legacy_dialect(&legacy, &dialect).to_plain_string(&translator),
+            "{#ff0000}hi {{#00ff00} &"
+        );
+
+        let dialect = LegacyDialect {
+            escape: Some(LegacyEscape::Backslash),
+            ..LegacyDialect::ampersand()
+        };
+        let chat = Chat::from_legacy_dialect("\\&ca&&", &dialect);
+        assert_eq!(chat.to_plain_string(&translator), "&ca&&");
+        assert_eq!(
+            chat.to_legacy_string_with(&translator, &dialect, LegacyHexColors::Exact),
+            "\\&ca\\&\\&"
+        );
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let dialect = LegacyDialect::section();
+        let text = "§c§lab§zc§";
+        let tokens = dialect
+            .tokenize(text)
+            .map(|token| (token.kind, &text[token.span]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                (LegacyTokenKind::Color(ChatColor::Red), "§c"),
+                (LegacyTokenKind::Format(ChatFormat::Bold), "§l"),
+                (LegacyTokenKind::Text, "ab"),
+                (LegacyTokenKind::Unknown('z'), "§z"),
+                (LegacyTokenKind::Text, "c"),
+                (LegacyTokenKind::Dangling, "§"),
+            ]
+        );
+        assert_eq!(
+            dialect.diagnostics(text),
+            vec![
+                LegacyError::UnknownCode {
+                    code: 'z',
+                    offset: 8
+                },
+                LegacyError::DanglingPrefix { offset: 12 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tree() {
+        let chat = Chat::from_legacy("§c§lab§ocd§lef§cgh§r§rij§6");
+        assert_eq!(
+            serde_json::to_value(&chat).unwrap(),
+            serde_json::json!({
+                "text": "",
+                "extra": [
+                    {
+                        "text": "ab",
+                        "bold": true,
+                        "color": "red",
+                        "extra": [{ "text": "cdef", "italic": true }]
+                    },
+                    { "text": "gh", "color": "red" },
+                    { "text": "ij" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_plain() {
+        let translator = Translator::default();
+        let chat: Chat = serde_json::from_str("\"Price: 5§a\"").unwrap();
+        assert_eq!(chat.to_plain_string(&translator), "Price: 5§a");
+        let chat: Chat =
+            serde_json::from_str(r#"{"text": "Price: 5§a", "extra": [" §cand§r more"]}"#).unwrap();
+        assert_eq!(chat.to_plain_string(&translator), "Price: 5§a §cand§r more");
+        assert_eq!(
+            chat.to_plain_string_with(&translator, true),
+            "Price: 5 and more"
+        );
+        // Only explicitly legacy text has formatting codes
+        assert_eq!(
+            Chat::Legacy("Price: 5§a".to_owned()).to_plain_string(&translator),
+            "Price: 5"
+        );
+    }
+}
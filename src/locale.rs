@@ -0,0 +1,318 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use once_cell::sync::OnceCell;
+
+use crate::{
+    error::LocaleError,
+    resource_pack::{LanguageInfo, ResourcePack},
+    translator::{TranslationProvider, Translator},
+};
+
+pub const DEFAULT_LOCALE: &str = "en_us";
+
+/// Normalize a locale code like en_US, en-us or EN_us to the vanilla form en_us
+pub fn normalize_locale(locale: &str) -> String {
+    locale.trim().to_ascii_lowercase().replace('-', "_")
+}
+
+/// Language part of a normalized locale (de for de_at)
+fn language(locale: &str) -> &str {
+    locale.split('_').next().unwrap_or(locale)
+}
+
+#[derive(Debug)]
+struct LocaleEntry {
+    /// File to load the translations from on first use
+    path: Option<PathBuf>,
+    translator: OnceCell<Translator>,
+}
+
+impl LocaleEntry {
+    fn load(&self, locale: &str) -> Result<&Translator, LocaleError> {
+        self.translator.get_or_try_init(|| {
+            let Some(path) = &self.path else {
+                return Ok(Translator::default());
+            };
+            let content = std::fs::read_to_string(path)?;
+            let content = content.trim_start_matches('\u{feff}');
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "lang")
+            {
+                Ok(Translator::from_lang_content(content))
+            } else {
+                Translator::from_translation_content(content).map_err(|source| {
+                    LocaleError::InvalidJson {
+                        locale: locale.to_owned(),
+                        source,
+                    }
+                })
+            }
+        })
+    }
+}
+
+/// Translators for many locales, falling back key by key (de_at → de_de → en_us)
+#[derive(Debug)]
+pub struct TranslatorSet {
+    locales: BTreeMap<String, LocaleEntry>,
+    default_locale: String,
+    fallbacks: HashMap<String, Vec<String>>,
+    languages: BTreeMap<String, LanguageInfo>,
+}
+
+impl Default for TranslatorSet {
+    fn default() -> Self {
+        Self {
+            locales: BTreeMap::new(),
+            default_locale: DEFAULT_LOCALE.to_owned(),
+            fallbacks: HashMap::new(),
+            languages: BTreeMap::new(),
+        }
+    }
+}
+
+impl TranslatorSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Find the <locale>.json and <locale>.lang files in a directory. They only get read
+    /// once a locale is used.
+    pub fn from_directory(path: impl AsRef<Path>) -> Result<Self, LocaleError> {
+        let mut set = Self::new();
+        set.add_directory(path)?;
+        Ok(set)
+    }
+
+    /// Add the locale files of a directory. Json files are preferred over .lang files.
+    pub fn add_directory(&mut self, path: impl AsRef<Path>) -> Result<(), LocaleError> {
+        let mut files: Vec<_> = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        files.sort_by_key(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        });
+        for path in files {
+            let is_locale_file = path
+                .extension()
+                .is_some_and(|extension| extension == "json" || extension == "lang");
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if is_locale_file && path.is_file() {
+                let entry = LocaleEntry {
+                    path: Some(path.clone()),
+                    translator: OnceCell::new(),
+                };
+                self.locales.insert(normalize_locale(locale), entry);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, locale: &str, translator: Translator) {
+        let entry = LocaleEntry {
+            path: None,
+            translator: OnceCell::with_value(translator),
+        };
+        self.locales.insert(normalize_locale(locale), entry);
+    }
+
+    /// Apply a resource pack on top of the current translations. Packs added later take
+    /// priority, like the ones higher up in the client's list.
+    pub fn add_resource_pack(&mut self, pack: &ResourcePack) -> Result<(), LocaleError> {
+        for (locale, translations) in &pack.translations {
+            let mut translator = match self.locales.get(locale) {
+                Some(entry) => entry.load(locale)?.clone(),
+                None => Translator::default(),
+            };
+            translator.overlay(translations);
+            self.insert(locale, translator);
+        }
+        self.languages.extend(
+            pack.languages
+                .iter()
+                .map(|(locale, info)| (locale.to_owned(), info.clone())),
+        );
+        Ok(())
+    }
+
+    /// Name, region and text direction of a locale, as declared by resource packs
+    pub fn language_info(&self, locale: &str) -> Option<&LanguageInfo> {
+        self.languages.get(&normalize_locale(locale))
+    }
+
+    /// The last resort of every fallback chain (en_us unless changed)
+    pub fn set_default_locale(&mut self, locale: &str) {
+        self.default_locale = normalize_locale(locale);
+    }
+
+    /// Use these locales instead of the language's main locale as fallbacks
+    pub fn set_fallbacks(&mut self, locale: &str, fallbacks: &[&str]) {
+        self.fallbacks.insert(
+            normalize_locale(locale),
+            fallbacks
+                .iter()
+                .map(|locale| normalize_locale(locale))
+                .collect(),
+        );
+    }
+
+    /// Normalized codes of the available locales
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.locales.keys().map(|locale| locale.as_str())
+    }
+
+    pub fn contains_locale(&self, locale: &str) -> bool {
+        self.locales.contains_key(&normalize_locale(locale))
+    }
+
+    /// Available locales to look keys up in for a client locale, best first: the locale itself,
+    /// its fallbacks (or the language's main locale, like de_de for de_at) and the default locale.
+    /// Without any of those, another locale of the same language is used.
+    pub fn fallback_chain(&self, locale: &str) -> Vec<&str> {
+        let locale = normalize_locale(locale);
+        let locale_language = language(&locale);
+        let main_locale = format!("{locale_language}_{locale_language}");
+        let mut candidates = vec![locale.as_str()];
+        match self.fallbacks.get(&locale) {
+            Some(fallbacks) => candidates.extend(fallbacks.iter().map(|locale| locale.as_str())),
+            None => candidates.push(&main_locale),
+        }
+
+        let mut chain: Vec<&str> = Vec::new();
+        for candidate in candidates {
+            if let Some((available, _)) = self.locales.get_key_value(candidate) {
+                if !chain.contains(&available.as_str()) {
+                    chain.push(available);
+                }
+            }
+        }
+        if chain.is_empty() {
+            chain.extend(
+                self.locales()
+                    .find(|available| language(available) == locale_language),
+            );
+        }
+        if let Some((available, _)) = self.locales.get_key_value(&self.default_locale) {
+            if !chain.contains(&available.as_str()) {
+                chain.push(available);
+            }
+        }
+        chain
+    }
+
+    /// The best available locale for a client locale
+    pub fn negotiate(&self, locale: &str) -> Option<&str> {
+        self.fallback_chain(locale).first().copied()
+    }
+
+    /// The translations of exactly this locale, loading them if needed
+    pub fn load(&self, locale: &str) -> Result<&Translator, LocaleError> {
+        let locale = normalize_locale(locale);
+        match self.locales.get(&locale) {
+            Some(entry) => entry.load(&locale),
+            None => Err(LocaleError::UnknownLocale { locale }),
+        }
+    }
+
+    /// Look a key up through the fallback chain of the locale
+    pub fn get(&self, locale: &str, translate: &str) -> Result<Option<&str>, LocaleError> {
+        for locale in self.fallback_chain(locale) {
+            if let Some(translation) = self.load(locale)?.get(translate) {
+                return Ok(Some(translation));
+            }
+        }
+        Ok(None)
+    }
+
+    /// A translator with every key of the fallback chain, for rendering in the locale
+    pub fn translator(&self, locale: &str) -> Result<Translator, LocaleError> {
+        let mut translator = Translator::default();
+        for locale in self.fallback_chain(locale) {
+            translator.fill_from(self.load(locale)?);
+        }
+        Ok(translator)
+    }
+}
+
+impl TranslationProvider for TranslatorSet {
+    /// Looks the key up through the fallback chain of the locale (the default locale if none).
+    /// Locales that fail to load get skipped, [`TranslatorSet::get`] reports those errors.
+    fn lookup(&self, translate: &str, locale: Option<&str>) -> Option<Cow<'_, str>> {
+        self.fallback_chain(locale.unwrap_or(&self.default_locale))
+            .into_iter()
+            .filter_map(|locale| self.load(locale).ok())
+            .find_map(|translator| translator.get(translate))
+            .map(Cow::Borrowed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translator(json: &str) -> Translator {
+        Translator::from_translation_content(json).unwrap()
+    }
+
+    #[test]
+    fn test_fallback_chain() {
+        let mut set = TranslatorSet::new();
+        set.insert(
+            "en_US",
+            translator(r#"{"a": "A", "b": "B", "c": "C", "d": "D"}"#),
+        );
+        set.insert("de-DE", translator(r#"{"a": "Ä", "b": "Bé"}"#));
+        set.insert("de_at", translator(r#"{"a": "Å"}"#));
+        set.insert("pt_br", translator(r#"{"c": "Cê"}"#));
+
+        assert_eq!(normalize_locale(" EN-us"), "en_us");
+        assert_eq!(set.fallback_chain("DE-at"), ["de_at", "de_de", "en_us"]);
+        assert_eq!(set.negotiate("de_ch"), Some("de_de"));
+        assert_eq!(set.negotiate("pt_pt"), Some("pt_br"));
+        assert_eq!(set.negotiate("fr_fr"), Some("en_us"));
+
+        let get = |locale, key| set.get(locale, key).unwrap();
+        assert_eq!(get("de_at", "a"), Some("Å"));
+        assert_eq!(get("de_at", "b"), Some("Bé"));
+        assert_eq!(get("de_at", "d"), Some("D"));
+        assert_eq!(get("de_at", "x"), None);
+        assert_eq!(set.translator("pt_pt").unwrap().get("c"), Some("Cê"));
+
+        set.set_fallbacks("de_at", &["pt_br"]);
+        assert_eq!(set.fallback_chain("de_at"), ["de_at", "pt_br", "en_us"]);
+    }
+
+    #[test]
+    fn test_directory() {
+        let directory =
+            std::env::temp_dir().join(format!("chat-formatting-locales-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("en_us.json"), r#"{"a": "A"}"#).unwrap();
+        std::fs::write(
+            directory.join("de_DE.lang"),
+            "# Comment\na=\\u00c4 %d %2$.1f=\\ud83d\\ude00\n",
+        )
+        .unwrap();
+        std::fs::write(directory.join("broken_xx.json"), "{").unwrap();
+
+        let set = TranslatorSet::from_directory(&directory).unwrap();
+        assert_eq!(
+            set.locales().collect::<Vec<_>>(),
+            ["broken_xx", "de_de", "en_us"]
+        );
+        assert_eq!(set.get("de_at", "a").unwrap(), Some("Ä %s %2$s=😀"));
+        assert!(matches!(
+            set.load("broken_xx"),
+            Err(LocaleError::InvalidJson { .. })
+        ));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use crate::{
+    chat::{Chat, ChatComponent, ClickAction, ClickEvent, TextContent},
+    color::ColorDistance,
+    formatting::{ChatColor, ChatFormat},
+    style::{TextRun, FORMATS},
+};
+
+/// Options for converting between Discord Markdown and chat
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiscordMarkdown {
+    /// Formats to render in place of colors, which markdown can't show. Colors without
+    /// an entry get dropped. Hex colors use the entry of their closest named color.
+    pub color_formats: HashMap<ChatColor, ChatFormat>,
+    /// Color for `inline code` and code blocks when parsing
+    pub code_color: Option<ChatColor>,
+}
+
+fn marker(format: ChatFormat) -> &'static str {
+    match format {
+        ChatFormat::Bold => "**",
+        ChatFormat::Italic => "*",
+        ChatFormat::Underlined => "__",
+        ChatFormat::Strikethrough => "~~",
+        ChatFormat::Obfuscated => "||",
+    }
+}
+
+/// Escape markdown metacharacters with a backslash
+fn escape_markdown(output: &mut String, text: &str) {
+    let mut line_start = output.is_empty() || output.ends_with('\n');
+    for chr in text.chars() {
+        let is_meta = matches!(chr, '\\' | '*' | '_' | '~' | '|' | '`' | '[' | ']')
+            // Quotes, headings and lists
+            || (line_start && matches!(chr, '>' | '#' | '-'));
+        if is_meta {
+            output.push('\\');
+        }
+        output.push(chr);
+        line_start = chr == '\n';
+    }
+}
+
+impl DiscordMarkdown {
+    fn formats(&self, run: &TextRun) -> Vec<ChatFormat> {
+        let fallback = run
+            .style
+            .color
+            .map(|color| color.to_named(ColorDistance::default()))
+            .and_then(|color| self.color_formats.get(&color));
+        FORMATS
+            .into_iter()
+            .filter(|format| run.style.has_format(*format) || fallback == Some(format))
+            .collect()
+    }
+
+    /// Write runs, opening and closing markers where formats change
+    fn write_runs(&self, output: &mut String, runs: &[TextRun]) {
+        let mut open: Vec<ChatFormat> = Vec::new();
+        for run in runs {
+            let formats = self.formats(run);
+            // Markers need to be closed in reverse order
+            let keep = open
+                .iter()
+                .position(|format| !formats.contains(format))
+                .unwrap_or(open.len());
+            for format in open.drain(keep..).rev() {
+                output.push_str(marker(format));
+            }
+            for format in formats {
+                if !open.contains(&format) {
+                    output.push_str(marker(format));
+                    open.push(format);
+                }
+            }
+            escape_markdown(output, &run.text);
+        }
+        for format in open.into_iter().rev() {
+            output.push_str(marker(format));
+        }
+    }
+}
+
+/// The url runs link to, if it can be a markdown link
+fn link_url(run: &TextRun) -> Option<&str> {
+    let click_event = run.style.click_event.as_ref()?;
+    let url = click_event.value.as_str();
+    (click_event.action == ClickAction::OpenUrl
+        && (url.starts_with("https://") || url.starts_with("http://")))
+    .then_some(url)
+}
+
+/// Render runs as Discord Markdown, with open_url click events as masked links
+pub(crate) fn runs_to_markdown(runs: &[TextRun], options: &DiscordMarkdown) -> String {
+    let mut output = String::new();
+    let mut runs = runs;
+    while let Some(first) = runs.first() {
+        let url = link_url(first);
+        let length = runs
+            .iter()
+            .position(|run| link_url(run) != url)
+            .unwrap_or(runs.len());
+        let (group, rest) = runs.split_at(length);
+        match url {
+            Some(url) => {
+                output.push('[');
+                options.write_runs(&mut output, group);
+                output.push_str(&format!("]({url})"));
+            }
+            None => options.write_runs(&mut output, group),
+        }
+        runs = rest;
+    }
+    output
+}
+
+struct MarkdownParser<'a> {
+    options: &'a DiscordMarkdown,
+}
+
+fn literal(text: &str) -> ChatComponent {
+    ChatComponent {
+        content: TextContent::new_literal(text),
+        ..Default::default()
+    }
+}
+
+/// Put the children inside a component, directly using the text of a single unstyled one
+fn wrap(mut children: Vec<ChatComponent>) -> ChatComponent {
+    if children.len() == 1 && children[0] == literal(literal_text(&children[0])) {
+        return children.remove(0);
+    }
+    ChatComponent {
+        extra: children.into_iter().map(Chat::Component).collect(),
+        ..Default::default()
+    }
+}
+
+fn literal_text(component: &ChatComponent) -> &str {
+    match &component.content {
+        TextContent::Literal { text } => text,
+        _ => "",
+    }
+}
+
+fn is_word_char(chr: Option<char>) -> bool {
+    chr.is_some_and(|chr| chr.is_alphanumeric())
+}
+
+impl MarkdownParser<'_> {
+    /// Find where the delimiter closing the one at the start of text starts
+    fn find_closing(text: &str, delimiter: &str) -> Option<usize> {
+        let delimiter_char = delimiter.chars().next().unwrap();
+        let mut index = delimiter.len();
+        while index < text.len() {
+            let rest = &text[index..];
+            if let Some(escaped) = rest.strip_prefix('\\') {
+                index += 1 + escaped.chars().next().map_or(0, char::len_utf8);
+                continue;
+            }
+            let run = rest.len() - rest.trim_start_matches(delimiter_char).len();
+            if run == 0 {
+                index += rest.chars().next().unwrap().len_utf8();
+                continue;
+            }
+            // Use the end of a longer run, so ***a*** is bold and italic
+            let closing = index + run - delimiter.len();
+            let intraword =
+                delimiter_char == '_' && is_word_char(text[index + run..].chars().next());
+            // Like Discord, italic asterisks need to be next to the text they surround
+            let spaced = delimiter == "*"
+                && (text[1..].starts_with(char::is_whitespace)
+                    || text[..closing].ends_with(char::is_whitespace));
+            if run >= delimiter.len() && closing > delimiter.len() && !intraword && !spaced {
+                return Some(closing);
+            }
+            index += run;
+        }
+        None
+    }
+
+    fn parse(&self, text: &str) -> Vec<ChatComponent> {
+        let mut children = Vec::new();
+        let mut plain = String::new();
+        let mut index = 0;
+        while index < text.len() {
+            let rest = &text[index..];
+            let previous = text[..index].chars().next_back();
+            if let Some(escaped) = rest
+                .strip_prefix('\\')
+                .and_then(|escaped| escaped.chars().next())
+                .filter(char::is_ascii_punctuation)
+            {
+                plain.push(escaped);
+                index += 2;
+                continue;
+            }
+            let Some((component, length)) = self.read_element(rest, previous) else {
+                let chr = rest.chars().next().unwrap();
+                plain.push(chr);
+                index += chr.len_utf8();
+                continue;
+            };
+            if !plain.is_empty() {
+                children.push(literal(&plain));
+                plain.clear();
+            }
+            children.push(component);
+            index += length;
+        }
+        if !plain.is_empty() {
+            children.push(literal(&plain));
+        }
+        children
+    }
+
+    /// Read the formatted element at the start of text, returning it with its length
+    fn read_element(&self, text: &str, previous: Option<char>) -> Option<(ChatComponent, usize)> {
+        let chr = text.chars().next()?;
+        if chr == '`' {
+            let ticks = text.len() - text.trim_start_matches('`').len();
+            let end = text[ticks..].find(&text[..ticks])?;
+            let mut component = literal(&text[ticks..ticks + end]);
+            component.color = self.options.code_color;
+            return Some((component, 2 * ticks + end));
+        }
+        if chr == '[' {
+            let label_end = text.find("](")?;
+            let url_end = label_end + text[label_end..].find(')')?;
+            let url = &text[label_end + 2..url_end];
+            if !(url.starts_with("https://") || url.starts_with("http://")) || url.contains(' ') {
+                return None;
+            }
+            let mut component = wrap(self.parse(&text[1..label_end]));
+            component.click_event = Some(ClickEvent {
+                action: ClickAction::OpenUrl,
+                value: url.to_owned(),
+            });
+            return Some((component, url_end + 1));
+        }
+        if (text.starts_with("https://") || text.starts_with("http://")) && !is_word_char(previous)
+        {
+            let end = text.find(char::is_whitespace).unwrap_or(text.len());
+            let mut component = literal(&text[..end]);
+            component.click_event = Some(ClickEvent {
+                action: ClickAction::OpenUrl,
+                value: text[..end].to_owned(),
+            });
+            return Some((component, end));
+        }
+
+        let (delimiter, format, closing) = [
+            ("||", ChatFormat::Obfuscated),
+            ("~~", ChatFormat::Strikethrough),
+            ("__", ChatFormat::Underlined),
+            ("**", ChatFormat::Bold),
+            ("*", ChatFormat::Italic),
+            ("_", ChatFormat::Italic),
+        ]
+        .into_iter()
+        .filter(|(delimiter, _)| text.starts_with(delimiter))
+        .filter(|(delimiter, _)| !delimiter.starts_with('_') || !is_word_char(previous))
+        .find_map(|(delimiter, format)| {
+            Some((delimiter, format, Self::find_closing(text, delimiter)?))
+        })?;
+
+        let mut component = wrap(self.parse(&text[delimiter.len()..closing]));
+        match format {
+            ChatFormat::Bold => component.bold = Some(true),
+            ChatFormat::Italic => component.italic = Some(true),
+            ChatFormat::Underlined => component.underlined = Some(true),
+            ChatFormat::Strikethrough => component.strikethrough = Some(true),
+            ChatFormat::Obfuscated => component.obfuscated = Some(true),
+        }
+        Some((component, closing + delimiter.len()))
+    }
+}
+
+impl Chat {
+    /// Parse Discord Markdown (bold, italic, underline, strikethrough, spoilers as obfuscated,
+    /// code, masked links and urls)
+    pub fn from_markdown(markdown: &str, options: &DiscordMarkdown) -> Chat {
+        let parser = MarkdownParser { options };
+        Chat::Component(wrap(parser.parse(markdown)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chat::TextFormatter, translator::Translator};
+
+    #[test]
+    fn test_to_markdown() {
+        let chat = Chat::from_minimessage(
+            "<b>Hi <i>there</i></b> <gold><obf>secret</obf> *stars* and_under</gold>\n<click:open_url:'https://example.com'><u>site</u></click> # not a heading",
+        );
+        let translator = Translator::default();
+        assert_eq!(
+            chat.to_markdown_string(&translator),
+            "**Hi *there*** ||secret|| \\*stars\\* and\\_under\n[__site__](https://example.com) # not a heading"
+        );
+        let options = DiscordMarkdown {
+            color_formats: HashMap::from([(ChatColor::Gold, ChatFormat::Bold)]),
+            ..Default::default()
+        };
+        assert_eq!(
+            Chat::from_minimessage("a<#FFA010>b").to_markdown_string_with(&translator, &options),
+            "a**b**"
+        );
+    }
+
+    #[test]
+    fn test_from_markdown() {
+        let options = DiscordMarkdown {
+            code_color: Some(ChatColor::Gray),
+            ..Default::default()
+        };
+        let chat = Chat::from_markdown(
+            "***both*** __under__ ~~st~~ ||spoiler|| my_snake_case `*code*` \\*no\\* [site](https://example.com) https://a.b/c",
+            &options,
+        );
+        assert_eq!(
+            chat.to_minimessage(),
+            "<bold><italic>both</italic></bold> <underlined>under</underlined> <strikethrough>st</strikethrough> <obfuscated>spoiler</obfuscated> my_snake_case <gray>*code*</gray> *no* <click:open_url:'https://example.com'>site</click> <click:open_url:'https://a.b/c'>https://a.b/c"
+        );
+        assert_eq!(
+            Chat::from_markdown("2 * 3 = 6, **unclosed", &options).to_minimessage(),
+            "2 * 3 = 6, **unclosed"
+        );
+    }
+}
@@ -0,0 +1,1279 @@
+use crate::{
+    chat::{
+        Chat, ChatComponent, ClickAction, ClickEvent, Score, TextContent, TranslatablePlaceholder,
+    },
+    error::MiniMessageError,
+    formatting::ChatColor,
+    hover::{HoverEntity, HoverEvent, HoverItem, Uuid},
+};
+
+/// Parser for the [MiniMessage](https://docs.advntr.dev/minimessage/format.html) format
+/// used by Adventure (Paper, Velocity and many plugins).
+///
+/// When lenient (default), unknown or invalid tags stay as literal text and unclosed tags
+/// get closed automatically. When strict, those cases are errors instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MiniMessage {
+    pub strict: bool,
+}
+
+#[derive(Debug)]
+struct Tag {
+    /// Lowercase name without a leading "/" or "!"
+    name: String,
+    negated: bool,
+    closing: bool,
+    self_closing: bool,
+    args: Vec<String>,
+    /// Offset in chars
+    offset: usize,
+    /// The tag as written, in case it turns out to be text
+    raw: String,
+}
+
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    Tag(Tag),
+}
+
+enum TagAction {
+    /// Opens a tag, which styles everything until closed
+    Style(ChatComponent, Option<ColorEffect>),
+    /// Inserts a component in place
+    Insert(ChatComponent),
+    Reset,
+}
+
+enum TagError {
+    Unknown,
+    InvalidArguments,
+    Nested(MiniMessageError),
+}
+
+enum ColorEffect {
+    Gradient { colors: Vec<[u8; 3]>, phase: f64 },
+    Rainbow { reversed: bool, phase: i32 },
+}
+
+struct Frame {
+    name: String,
+    offset: usize,
+    component: ChatComponent,
+    effect: Option<ColorEffect>,
+}
+
+fn is_tag_name_char(chr: char) -> bool {
+    chr.is_ascii_alphanumeric() || matches!(chr, '_' | '-' | '!' | '#' | '?')
+}
+
+fn canonical_name(name: &str) -> &str {
+    match name {
+        "colour" | "c" => "color",
+        "b" => "bold",
+        "em" | "i" => "italic",
+        "u" => "underlined",
+        "st" => "strikethrough",
+        "obf" => "obfuscated",
+        "insert" => "insertion",
+        "key" => "keybind",
+        "tr" | "translate" => "lang",
+        "tr_or" | "translate_or" => "lang_or",
+        "br" => "newline",
+        "sel" => "selector",
+        name => name,
+    }
+}
+
+fn parse_tag(chars: &[char], start: usize) -> Option<(Tag, usize)> {
+    let mut i = start + 1;
+    let closing = chars.get(i) == Some(&'/');
+    if closing {
+        i += 1;
+    }
+
+    let mut self_closing = false;
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut quote = None;
+    loop {
+        let chr = *chars.get(i)?;
+        i += 1;
+        match quote {
+            Some(quote_chr) => {
+                let next = chars.get(i).copied();
+                if chr == '\\' && (next == Some(quote_chr) || next == Some('\\')) {
+                    part.push(next.unwrap());
+                    i += 1;
+                } else if chr == quote_chr {
+                    quote = None;
+                } else {
+                    part.push(chr);
+                }
+            }
+            None => match chr {
+                '\'' | '"' => quote = Some(chr),
+                ':' => parts.push(std::mem::take(&mut part)),
+                '/' if !closing && chars.get(i) == Some(&'>') => self_closing = true,
+                '>' => {
+                    parts.push(part);
+                    break;
+                }
+                '<' => return None,
+                _ => part.push(chr),
+            },
+        }
+    }
+
+    let name = parts.remove(0).to_lowercase();
+    if name.is_empty() || !name.chars().all(is_tag_name_char) {
+        return None;
+    }
+    let (negated, name) = match name.strip_prefix('!') {
+        Some(name) => (true, name.to_owned()),
+        None => (false, name),
+    };
+    Some((
+        Tag {
+            name: canonical_name(&name).to_owned(),
+            negated,
+            closing,
+            self_closing,
+            args: parts,
+            offset: start,
+            raw: chars[start..i].iter().collect(),
+        },
+        i,
+    ))
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if matches!(chars.get(i + 1), Some('<' | '\\')) => {
+                text.push(chars[i + 1]);
+                i += 2;
+            }
+            '<' => match parse_tag(&chars, i) {
+                Some((tag, end)) => {
+                    if !text.is_empty() {
+                        tokens.push(Token::Text(std::mem::take(&mut text)));
+                    }
+                    tokens.push(Token::Tag(tag));
+                    i = end;
+                }
+                None => {
+                    text.push('<');
+                    i += 1;
+                }
+            },
+            chr => {
+                text.push(chr);
+                i += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    tokens
+}
+
+fn parse_color(name: &str) -> Option<ChatColor> {
+    match name {
+        "reset" => None,
+        "grey" => Some(ChatColor::Gray),
+        "dark_grey" => Some(ChatColor::DarkGray),
+        _ if name.starts_with('#') => ChatColor::from_hex_str(name).ok(),
+        _ => ChatColor::from_color_name(name).ok(),
+    }
+}
+
+fn parse_click_action(action: &str) -> Option<ClickAction> {
+    Some(match action {
+        "open_url" => ClickAction::OpenUrl,
+        "open_file" => ClickAction::OpenFile,
+        "run_command" => ClickAction::RunCommand,
+        "suggest_command" => ClickAction::SuggestCommand,
+        "change_page" => ClickAction::ChangePage,
+        "copy_to_clipboard" => ClickAction::CopyToClipboard,
+        _ => return None,
+    })
+}
+
+/// Unquoted resource locations (minecraft:stone) get split up as arguments. Join them again.
+fn take_resource_location(
+    args: &[String],
+    is_next_arg: impl Fn(&str) -> bool,
+) -> (String, &[String]) {
+    match args {
+        [namespace, path, rest @ ..] if !is_next_arg(path) => {
+            (format!("{}:{}", namespace, path), rest)
+        }
+        [id, rest @ ..] => (id.to_owned(), rest),
+        [] => (String::new(), args),
+    }
+}
+
+fn is_uuid(string: &str) -> bool {
+    string.len() == 36 && string.chars().filter(|chr| *chr == '-').count() == 4
+}
+
+fn interpolate(from: [u8; 3], to: [u8; 3], t: f64) -> [u8; 3] {
+    let channel = |i: usize| (from[i] as f64 + (to[i] as f64 - from[i] as f64) * t).round() as u8;
+    [channel(0), channel(1), channel(2)]
+}
+
+fn hue_to_rgb(hue: f64) -> [u8; 3] {
+    let sector = hue * 6.0;
+    let x = 1.0 - ((sector % 2.0) - 1.0).abs();
+    let (r, g, b) = match sector as u8 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let channel = |value: f64| (value * 255.0).round() as u8;
+    [channel(r), channel(g), channel(b)]
+}
+
+impl ColorEffect {
+    fn color_at(&self, index: usize, size: usize) -> ChatColor {
+        match self {
+            ColorEffect::Gradient { colors, phase } => {
+                let mut colors = colors.clone();
+                let mut phase = *phase;
+                if phase < 0.0 {
+                    colors.reverse();
+                    phase += 1.0;
+                }
+                let steps = (colors.len() - 1) as f64;
+                let multiplier = if size <= 1 {
+                    0.0
+                } else {
+                    steps / (size - 1) as f64
+                };
+                let position = index as f64 * multiplier + phase * steps;
+                let low = position.floor();
+                let low_index = (low as usize) % colors.len();
+                let high_index = (low_index + 1) % colors.len();
+                ChatColor::Hex(interpolate(
+                    colors[low_index],
+                    colors[high_index],
+                    position - low,
+                ))
+            }
+            ColorEffect::Rainbow { reversed, phase } => {
+                let progress = index as f64 / size.max(1) as f64;
+                let hue = (progress + *phase as f64 / 10.0).rem_euclid(1.0);
+                ChatColor::Hex(hue_to_rgb(if *reversed { 1.0 - hue } else { hue }))
+            }
+        }
+    }
+
+    fn apply(&self, component: &mut ChatComponent) {
+        let size = count_chars(component);
+        let mut index = 0;
+        colorize(component, &mut || {
+            let color = self.color_at(index, size);
+            index += 1;
+            color
+        });
+    }
+}
+
+fn count_chars(component: &ChatComponent) -> usize {
+    let own = match &component.content {
+        TextContent::Literal { text } => text.chars().count(),
+        _ => 1,
+    };
+    own + component
+        .extra
+        .iter()
+        .map(|extra| match extra {
+            Chat::Component(component) => count_chars(component),
+            _ => 0,
+        })
+        .sum::<usize>()
+}
+
+fn colorize(component: &mut ChatComponent, next_color: &mut impl FnMut() -> ChatColor) {
+    match &component.content {
+        TextContent::Literal { text } if text.chars().count() > 1 => {
+            let chars = text
+                .chars()
+                .map(|chr| {
+                    Chat::Component(ChatComponent {
+                        content: TextContent::new_literal(&chr.to_string()),
+                        color: Some(next_color()),
+                        ..Default::default()
+                    })
+                })
+                .collect::<Vec<_>>();
+            component.content = TextContent::default();
+            for extra in &mut component.extra {
+                if let Chat::Component(extra) = extra {
+                    colorize(extra, next_color);
+                }
+            }
+            component.extra.splice(0..0, chars);
+            return;
+        }
+        TextContent::Literal { text } if text.is_empty() => {}
+        _ => component.color = Some(next_color()),
+    }
+    for extra in &mut component.extra {
+        if let Chat::Component(extra) = extra {
+            colorize(extra, next_color);
+        }
+    }
+}
+
+fn is_plain_text(component: &ChatComponent) -> bool {
+    matches!(component.content, TextContent::Literal { .. })
+        && ChatComponent {
+            content: component.content.clone(),
+            ..Default::default()
+        } == *component
+}
+
+fn push_text(frame: &mut Frame, text: &str) {
+    if let Some(Chat::Component(last)) = frame.component.extra.last_mut() {
+        if let (true, TextContent::Literal { text: last_text }) =
+            (is_plain_text(last), &mut last.content)
+        {
+            last_text.push_str(text);
+            return;
+        }
+    }
+    frame.component.extra.push(Chat::Component(ChatComponent {
+        content: TextContent::new_literal(text),
+        ..Default::default()
+    }));
+}
+
+/// Move leading plain text into the content of an otherwise empty component
+fn fold(mut component: ChatComponent) -> ChatComponent {
+    let content_empty = component.content == TextContent::default();
+    if let (true, Some(Chat::Component(first))) = (content_empty, component.extra.first()) {
+        if is_plain_text(first) {
+            let Chat::Component(first) = component.extra.remove(0) else {
+                unreachable!()
+            };
+            component.content = first.content;
+        }
+    }
+    component
+}
+
+fn close_frame(frames: &mut Vec<Frame>) {
+    let frame = frames.pop().expect("Root frame never gets closed");
+    if frame.component.extra.is_empty() {
+        return;
+    }
+    let mut component = fold(frame.component);
+    if let Some(effect) = frame.effect {
+        effect.apply(&mut component);
+    }
+    let parent = frames.last_mut().expect("Root frame never gets closed");
+    parent.component.extra.push(Chat::Component(component));
+}
+
+impl MiniMessage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn strict() -> Self {
+        Self { strict: true }
+    }
+
+    pub fn parse(&self, input: &str) -> Result<Chat, MiniMessageError> {
+        let mut frames = vec![Frame {
+            name: String::new(),
+            offset: 0,
+            component: ChatComponent::default(),
+            effect: None,
+        }];
+
+        for token in tokenize(input) {
+            let tag = match token {
+                Token::Text(text) => {
+                    push_text(frames.last_mut().unwrap(), &text);
+                    continue;
+                }
+                Token::Tag(tag) => tag,
+            };
+
+            if tag.closing {
+                let Some(index) = frames.iter().rposition(|frame| frame.name == tag.name) else {
+                    if self.strict {
+                        return Err(MiniMessageError::UnmatchedClosingTag {
+                            tag: tag.name,
+                            offset: tag.offset,
+                        });
+                    }
+                    // Like Adventure, keep closing tags that close nothing as text
+                    push_text(frames.last_mut().unwrap(), &tag.raw);
+                    continue;
+                };
+                if index == 0 {
+                    continue;
+                }
+                if self.strict && index != frames.len() - 1 {
+                    let unclosed = frames.last().unwrap();
+                    return Err(MiniMessageError::UnclosedTag {
+                        tag: unclosed.name.clone(),
+                        offset: unclosed.offset,
+                    });
+                }
+                while frames.len() > index {
+                    close_frame(&mut frames);
+                }
+                continue;
+            }
+
+            match self.resolve(&tag) {
+                Ok(TagAction::Style(component, effect)) => {
+                    if !tag.self_closing {
+                        frames.push(Frame {
+                            name: tag.name,
+                            offset: tag.offset,
+                            component,
+                            effect,
+                        });
+                    }
+                }
+                Ok(TagAction::Insert(component)) => frames
+                    .last_mut()
+                    .unwrap()
+                    .component
+                    .extra
+                    .push(Chat::Component(component)),
+                Ok(TagAction::Reset) => {
+                    while frames.len() > 1 {
+                        close_frame(&mut frames);
+                    }
+                }
+                Err(TagError::Nested(err)) => return Err(err),
+                Err(err) if self.strict => {
+                    return Err(match err {
+                        TagError::Unknown => MiniMessageError::UnknownTag {
+                            tag: tag.name,
+                            offset: tag.offset,
+                        },
+                        _ => MiniMessageError::InvalidArguments {
+                            tag: tag.name,
+                            offset: tag.offset,
+                        },
+                    })
+                }
+                Err(_) => push_text(frames.last_mut().unwrap(), &tag.raw),
+            }
+        }
+
+        if self.strict && frames.len() > 1 {
+            let unclosed = frames.last().unwrap();
+            return Err(MiniMessageError::UnclosedTag {
+                tag: unclosed.name.clone(),
+                offset: unclosed.offset,
+            });
+        }
+        while frames.len() > 1 {
+            close_frame(&mut frames);
+        }
+
+        let mut root = fold(frames.pop().unwrap().component);
+        if root.content == TextContent::default() && root.extra.len() == 1 {
+            if let Some(Chat::Component(only)) = root.extra.pop() {
+                root = only;
+            }
+        }
+        Ok(Chat::Component(root))
+    }
+
+    fn parse_nested(&self, input: &str) -> Result<Chat, TagError> {
+        self.parse(input).map_err(TagError::Nested)
+    }
+
+    fn resolve(&self, tag: &Tag) -> Result<TagAction, TagError> {
+        let args = tag.args.as_slice();
+        let style = |component: ChatComponent| Ok(TagAction::Style(component, None));
+        let insert = |content: TextContent| {
+            Ok(TagAction::Insert(ChatComponent {
+                content,
+                ..Default::default()
+            }))
+        };
+
+        if let Some(color) = parse_color(&tag.name).filter(|_| args.is_empty()) {
+            return style(ChatComponent {
+                color: Some(color),
+                ..Default::default()
+            });
+        }
+
+        match tag.name.as_str() {
+            "color" => match args {
+                [color] => style(ChatComponent {
+                    color: Some(
+                        parse_color(&color.to_lowercase()).ok_or(TagError::InvalidArguments)?,
+                    ),
+                    ..Default::default()
+                }),
+                _ => Err(TagError::InvalidArguments),
+            },
+            "bold" | "italic" | "underlined" | "strikethrough" | "obfuscated" => {
+                let value = match args {
+                    [] => Some(!tag.negated),
+                    [value] if value == "true" => Some(!tag.negated),
+                    [value] if value == "false" => Some(tag.negated),
+                    _ => return Err(TagError::InvalidArguments),
+                };
+                let mut component = ChatComponent::default();
+                match tag.name.as_str() {
+                    "bold" => component.bold = value,
+                    "italic" => component.italic = value,
+                    "underlined" => component.underlined = value,
+                    "strikethrough" => component.strikethrough = value,
+                    _ => component.obfuscated = value,
+                }
+                style(component)
+            }
+            "reset" => Ok(TagAction::Reset),
+            "click" => match args {
+                [action, value @ ..] if !value.is_empty() => style(ChatComponent {
+                    click_event: Some(ClickEvent {
+                        action: parse_click_action(action).ok_or(TagError::InvalidArguments)?,
+                        value: value.join(":"),
+                    }),
+                    ..Default::default()
+                }),
+                _ => Err(TagError::InvalidArguments),
+            },
+            "hover" => {
+                let hover_event = match args {
+                    [action, rest @ ..] if action == "show_text" && !rest.is_empty() => {
+                        HoverEvent::ShowText(Box::new(self.parse_nested(&rest.join(":"))?))
+                    }
+                    [action, rest @ ..] if action == "show_item" && !rest.is_empty() => {
+                        let (id, rest) =
+                            take_resource_location(rest, |arg| arg.parse::<i32>().is_ok());
+                        HoverEvent::ShowItem(HoverItem {
+                            id,
+                            count: match rest.first() {
+                                Some(count) => {
+                                    count.parse().map_err(|_| TagError::InvalidArguments)?
+                                }
+                                None => 1,
+                            },
+                            components: None,
+                            tag: rest.get(1).cloned(),
+                        })
+                    }
+                    [action, rest @ ..] if action == "show_entity" && rest.len() >= 2 => {
+                        let (entity_type, rest) = take_resource_location(rest, is_uuid);
+                        let [id, name @ ..] = rest else {
+                            return Err(TagError::InvalidArguments);
+                        };
+                        HoverEvent::ShowEntity(HoverEntity {
+                            entity_type,
+                            id: Uuid::parse(id).ok_or(TagError::InvalidArguments)?,
+                            name: match name.is_empty() {
+                                true => None,
+                                false => Some(Box::new(self.parse_nested(&name.join(":"))?)),
+                            },
+                        })
+                    }
+                    _ => return Err(TagError::InvalidArguments),
+                };
+                style(ChatComponent {
+                    hover_event: Some(hover_event),
+                    ..Default::default()
+                })
+            }
+            "insertion" if !args.is_empty() => style(ChatComponent {
+                insertion: Some(args.join(":")),
+                ..Default::default()
+            }),
+            "font" if !args.is_empty() => style(ChatComponent {
+                font: Some(args.join(":")),
+                ..Default::default()
+            }),
+            "gradient" => {
+                let (colors, phase) = match args.last().map(|arg| arg.parse::<f64>()) {
+                    Some(Ok(phase)) => (&args[..args.len() - 1], phase),
+                    _ => (args, 0.0),
+                };
+                let colors = match colors {
+                    [] => vec![[0xFF, 0xFF, 0xFF], [0x00, 0x00, 0x00]],
+                    [_] => return Err(TagError::InvalidArguments),
+                    colors => colors
+                        .iter()
+                        .map(|color| parse_color(&color.to_lowercase()).and_then(ChatColor::rgb))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or(TagError::InvalidArguments)?,
+                };
+                if !(-1.0..=1.0).contains(&phase) {
+                    return Err(TagError::InvalidArguments);
+                }
+                Ok(TagAction::Style(
+                    ChatComponent::default(),
+                    Some(ColorEffect::Gradient { colors, phase }),
+                ))
+            }
+            "rainbow" => {
+                let (reversed, phase) = match args {
+                    [] => (false, ""),
+                    [arg] => match arg.strip_prefix('!') {
+                        Some(phase) => (true, phase),
+                        None => (false, arg.as_str()),
+                    },
+                    _ => return Err(TagError::InvalidArguments),
+                };
+                let phase = match phase {
+                    "" => 0,
+                    phase => phase.parse().map_err(|_| TagError::InvalidArguments)?,
+                };
+                Ok(TagAction::Style(
+                    ChatComponent::default(),
+                    Some(ColorEffect::Rainbow { reversed, phase }),
+                ))
+            }
+            "newline" if args.is_empty() => insert(TextContent::new_literal("\n")),
+            "keybind" => match args {
+                [keybind] => insert(TextContent::Keybind {
+                    keybind: keybind.to_owned(),
+                }),
+                _ => Err(TagError::InvalidArguments),
+            },
+            "lang" | "lang_or" => {
+                let (translate, fallback, with) = match (tag.name.as_str(), args) {
+                    ("lang", [translate, with @ ..]) => (translate, None, with),
+                    ("lang_or", [translate, fallback, with @ ..]) => {
+                        (translate, Some(fallback.to_owned()), with)
+                    }
+                    _ => return Err(TagError::InvalidArguments),
+                };
+                let with = with
+                    .iter()
+                    .map(|arg| self.parse_nested(arg).map(TranslatablePlaceholder::Chat))
+                    .collect::<Result<Vec<_>, _>>()?;
+                insert(TextContent::Translatable {
+                    translate: translate.to_owned(),
+                    with: if with.is_empty() { None } else { Some(with) },
+                    fallback,
+                })
+            }
+            "selector" => match args {
+                [selector, separator @ ..] if separator.len() <= 1 => {
+                    insert(TextContent::EntityNamesSelector {
+                        selector: selector.to_owned(),
+                        separator: match separator.first() {
+                            Some(separator) => {
+                                Some(Box::new(self.parse_nested(separator)?.into_component()))
+                            }
+                            None => None,
+                        },
+                    })
+                }
+                _ => Err(TagError::InvalidArguments),
+            },
+            "score" => match args {
+                [name, objective] => insert(TextContent::ScoreboardValue {
+                    score: Score {
+                        name: name.to_owned(),
+                        objective: objective.to_owned(),
+                        value: None,
+                    },
+                }),
+                _ => Err(TagError::InvalidArguments),
+            },
+            "nbt" | "data" => {
+                let [source, id, path, rest @ ..] = args else {
+                    return Err(TagError::InvalidArguments);
+                };
+                let interpret = rest.last().is_some_and(|arg| arg == "interpret");
+                let separator = match rest {
+                    [separator] | [separator, _] if !(interpret && rest.len() == 1) => {
+                        Some(Box::new(self.parse_nested(separator)?.into_component()))
+                    }
+                    [] | [_] => None,
+                    _ => return Err(TagError::InvalidArguments),
+                };
+                let (block, entity, storage) = match source.as_str() {
+                    "block" => (Some(id.to_owned()), None, None),
+                    "entity" => (None, Some(id.to_owned()), None),
+                    "storage" => (None, None, Some(id.to_owned())),
+                    _ => return Err(TagError::InvalidArguments),
+                };
+                insert(TextContent::Nbt {
+                    nbt: path.to_owned(),
+                    interpret,
+                    separator,
+                    block,
+                    entity,
+                    storage,
+                })
+            }
+            _ => Err(TagError::Unknown),
+        }
+    }
+}
+
+/// Decorations in the order of [ChatComponent] fields and their MiniMessage tag names
+const DECORATIONS: [&str; 5] = [
+    "bold",
+    "italic",
+    "underlined",
+    "strikethrough",
+    "obfuscated",
+];
+
+fn decorations(component: &ChatComponent) -> [Option<bool>; 5] {
+    [
+        component.bold,
+        component.italic,
+        component.underlined,
+        component.strikethrough,
+        component.obfuscated,
+    ]
+}
+
+fn click_action_name(action: ClickAction) -> &'static str {
+    match action {
+        ClickAction::OpenUrl => "open_url",
+        ClickAction::OpenFile => "open_file",
+        ClickAction::RunCommand => "run_command",
+        ClickAction::SuggestCommand => "suggest_command",
+        ClickAction::ChangePage => "change_page",
+        ClickAction::CopyToClipboard => "copy_to_clipboard",
+    }
+}
+
+/// Quote an argument, if it would otherwise not survive parsing
+fn quote_arg(arg: &str) -> String {
+    let needs_quotes =
+        arg.is_empty() || arg.ends_with('/') || arg.contains([':', '<', '>', '\'', '"', '\\']);
+    if !needs_quotes {
+        return arg.to_owned();
+    }
+    let mut quoted = String::from("'");
+    for chr in arg.chars() {
+        if chr == '\'' || chr == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(chr);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('<', "\\<")
+}
+
+fn color_tag(color: ChatColor) -> String {
+    color.to_string().to_lowercase()
+}
+
+/// Style explicitly set by parent components. Used to skip tags that would change nothing.
+/// Unset values are not assumed to be false, as the context might have a different default
+/// (like item lore being italic).
+#[derive(Clone, Copy, Default)]
+struct InheritedStyle {
+    color: Option<ChatColor>,
+    decorations: [Option<bool>; 5],
+}
+
+#[derive(Default)]
+struct Serializer {
+    output: String,
+    /// Closing tags are only written once something follows them
+    pending_closing_tags: String,
+    /// Tags (without brackets) currently in effect, outermost first
+    open_tags: Vec<String>,
+}
+
+impl Serializer {
+    fn write(&mut self, string: &str) {
+        self.output.push_str(&self.pending_closing_tags);
+        self.pending_closing_tags.clear();
+        self.output.push_str(string);
+    }
+
+    fn chat(&mut self, chat: &Chat, inherited: InheritedStyle) {
+        match chat {
+            Chat::Text(_) => self.component(&chat.clone().into_component(), inherited),
+            Chat::Legacy(text) => {
+                self.component(&Chat::from_legacy(text).into_component(), inherited)
+            }
+            Chat::Component(component) => self.component(component, inherited),
+            Chat::Components(components) => {
+                for component in components {
+                    self.component(component, inherited);
+                }
+            }
+        }
+    }
+
+    fn component(&mut self, component: &ChatComponent, inherited: InheritedStyle) {
+        let mut style = inherited;
+        let mut closing_tags = Vec::new();
+
+        // MiniMessage can only reset all styles at once, so the others get reopened
+        let resets_color = component.color == Some(ChatColor::Reset) && style.color.is_some();
+        let parent_tags = resets_color.then(|| self.open_tags.clone());
+        if resets_color {
+            self.open_tags.retain(|tag| parse_color(tag).is_none());
+            let reopened: String = self
+                .open_tags
+                .iter()
+                .map(|tag| format!("<{}>", tag))
+                .collect();
+            self.write(&format!("<reset>{}", reopened));
+            style.color = None;
+        }
+
+        let mut open = |serializer: &mut Self, tag: String, name: &str| {
+            serializer.write(&format!("<{}>", tag));
+            serializer.open_tags.push(tag);
+            closing_tags.push(format!("</{}>", name));
+        };
+
+        if let Some(color) = component.color.filter(|color| *color != ChatColor::Reset) {
+            if style.color != Some(color) {
+                let tag = color_tag(color);
+                open(self, tag.clone(), &tag);
+                style.color = Some(color);
+            }
+        }
+        for (i, decoration) in decorations(component).into_iter().enumerate() {
+            match decoration {
+                Some(value) if Some(value) != style.decorations[i] => {
+                    let name = DECORATIONS[i];
+                    let tag = if value {
+                        name.to_owned()
+                    } else {
+                        format!("!{}", name)
+                    };
+                    open(self, tag, name);
+                    style.decorations[i] = Some(value);
+                }
+                _ => {}
+            }
+        }
+        if let Some(font) = &component.font {
+            open(self, format!("font:{}", quote_arg(font)), "font");
+        }
+        if let Some(insertion) = &component.insertion {
+            open(
+                self,
+                format!("insertion:{}", quote_arg(insertion)),
+                "insertion",
+            );
+        }
+        if let Some(click_event) = &component.click_event {
+            open(
+                self,
+                format!(
+                    "click:{}:{}",
+                    click_action_name(click_event.action),
+                    quote_arg(&click_event.value)
+                ),
+                "click",
+            );
+        }
+        if let Some(hover_tag) = component.hover_event.as_ref().and_then(hover_tag) {
+            open(self, hover_tag, "hover");
+        }
+
+        self.content(&component.content);
+        for extra in &component.extra {
+            self.chat(extra, style);
+        }
+
+        match parent_tags {
+            // Everything got closed by the reset, so the parent styles need to be restored
+            Some(parent_tags) => {
+                self.pending_closing_tags = "<reset>".to_owned();
+                for tag in &parent_tags {
+                    self.pending_closing_tags.push_str(&format!("<{}>", tag));
+                }
+                self.open_tags = parent_tags;
+            }
+            None => {
+                for closing_tag in closing_tags.iter().rev() {
+                    self.pending_closing_tags.push_str(closing_tag);
+                }
+                self.open_tags
+                    .truncate(self.open_tags.len() - closing_tags.len());
+            }
+        }
+    }
+
+    fn content(&mut self, content: &TextContent) {
+        match content {
+            TextContent::Literal { text } => {
+                if !text.is_empty() {
+                    self.write(&escape_text(text));
+                }
+            }
+            TextContent::Keybind { keybind } => {
+                self.write(&format!("<key:{}>", quote_arg(keybind)))
+            }
+            TextContent::Translatable {
+                translate,
+                with,
+                fallback,
+            } => {
+                let mut tag = match fallback {
+                    Some(fallback) => {
+                        format!("<lang_or:{}:{}", quote_arg(translate), quote_arg(fallback))
+                    }
+                    None => format!("<lang:{}", quote_arg(translate)),
+                };
+                for arg in with.iter().flatten() {
+                    let arg = match arg {
+                        TranslatablePlaceholder::Chat(chat) => chat.to_minimessage(),
+                        TranslatablePlaceholder::Number(number) => number.to_string(),
+                    };
+                    tag.push(':');
+                    tag.push_str(&quote_arg(&arg));
+                }
+                tag.push('>');
+                self.write(&tag);
+            }
+            TextContent::EntityNamesSelector {
+                selector,
+                separator,
+            } => {
+                let mut tag = format!("<selector:{}", quote_arg(selector));
+                if let Some(separator) = separator {
+                    tag.push(':');
+                    tag.push_str(&quote_arg(&separator.to_minimessage()));
+                }
+                tag.push('>');
+                self.write(&tag);
+            }
+            TextContent::ScoreboardValue { score } => self.write(&format!(
+                "<score:{}:{}>",
+                quote_arg(&score.name),
+                quote_arg(&score.objective)
+            )),
+            TextContent::Nbt {
+                nbt,
+                interpret,
+                separator,
+                block,
+                entity,
+                storage,
+            } => {
+                let source = match (block, entity, storage) {
+                    (Some(block), _, _) => ("block", block),
+                    (_, Some(entity), _) => ("entity", entity),
+                    (_, _, Some(storage)) => ("storage", storage),
+                    _ => return,
+                };
+                let mut tag = format!(
+                    "<nbt:{}:{}:{}",
+                    source.0,
+                    quote_arg(source.1),
+                    quote_arg(nbt)
+                );
+                if let Some(separator) = separator {
+                    tag.push(':');
+                    tag.push_str(&quote_arg(&separator.to_minimessage()));
+                }
+                if *interpret {
+                    tag.push_str(":interpret");
+                }
+                tag.push('>');
+                self.write(&tag);
+            }
+        }
+    }
+}
+
+/// Items with data components (1.20.5 and newer) have no MiniMessage form, so they are left out
+/// instead of losing their data
+fn hover_tag(hover_event: &HoverEvent) -> Option<String> {
+    Some(match hover_event {
+        HoverEvent::ShowText(text) => {
+            format!("hover:show_text:{}", quote_arg(&text.to_minimessage()))
+        }
+        HoverEvent::ShowItem(item) if item.components.is_some() => return None,
+        HoverEvent::ShowItem(item) => {
+            let mut tag = format!("hover:show_item:{}", quote_arg(&item.id));
+            if item.count != 1 || item.tag.is_some() {
+                tag.push_str(&format!(":{}", item.count));
+            }
+            if let Some(nbt) = &item.tag {
+                tag.push(':');
+                tag.push_str(&quote_arg(nbt));
+            }
+            tag
+        }
+        HoverEvent::ShowEntity(entity) => {
+            let mut tag = format!(
+                "hover:show_entity:{}:{}",
+                quote_arg(&entity.entity_type),
+                entity.id
+            );
+            if let Some(name) = &entity.name {
+                tag.push(':');
+                tag.push_str(&quote_arg(&name.to_minimessage()));
+            }
+            tag
+        }
+    })
+}
+
+impl Chat {
+    /// Parse MiniMessage leniently. Use [MiniMessage] for strict parsing.
+    pub fn from_minimessage(input: &str) -> Self {
+        MiniMessage::new()
+            .parse(input)
+            .expect("Lenient parsing never fails")
+    }
+
+    pub fn to_minimessage(&self) -> String {
+        let mut serializer = Serializer::default();
+        serializer.chat(self, InheritedStyle::default());
+        serializer.output
+    }
+}
+
+impl ChatComponent {
+    pub fn to_minimessage(&self) -> String {
+        let mut serializer = Serializer::default();
+        serializer.component(self, InheritedStyle::default());
+        serializer.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chat::TextFormatter, translator::Translator};
+
+    fn literal(text: &str) -> TextContent {
+        TextContent::new_literal(text)
+    }
+
+    #[test]
+    fn test_styles() {
+        let chat = Chat::from_minimessage("<red>Hi <bold><!italic>there</bold> \\<3</red>!");
+        assert_eq!(
+            chat,
+            Chat::Component(ChatComponent {
+                extra: vec![
+                    Chat::Component(ChatComponent {
+                        content: literal("Hi "),
+                        color: Some(ChatColor::Red),
+                        extra: vec![
+                            Chat::Component(ChatComponent {
+                                bold: Some(true),
+                                extra: vec![Chat::Component(ChatComponent {
+                                    content: literal("there"),
+                                    italic: Some(false),
+                                    ..Default::default()
+                                })],
+                                ..Default::default()
+                            }),
+                            Chat::Component(ChatComponent {
+                                content: literal(" <3"),
+                                ..Default::default()
+                            }),
+                        ],
+                        ..Default::default()
+                    }),
+                    Chat::Component(ChatComponent {
+                        content: literal("!"),
+                        ..Default::default()
+                    }),
+                ],
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_events_and_insertions() {
+        let component = Chat::from_minimessage(
+            "<hover:show_text:'<green>x'><click:open_url:https://example.com>a</click></hover><lang:chat.type.text:'<#ff00aa>b':c><newline>",
+        )
+        .into_component();
+        let Chat::Component(hovered) = &component.extra[0] else {
+            panic!("Expected component")
+        };
+        assert_eq!(
+            hovered.hover_event,
+            Some(HoverEvent::ShowText(Box::new(Chat::Component(
+                ChatComponent {
+                    content: TextContent::new_literal("x"),
+                    color: Some(ChatColor::Green),
+                    ..Default::default()
+                }
+            ))))
+        );
+        assert_eq!(
+            hovered.extra[0].clone().into_component().click_event,
+            Some(ClickEvent {
+                action: ClickAction::OpenUrl,
+                value: "https://example.com".to_owned(),
+            })
+        );
+        let Chat::Component(translatable) = &component.extra[1] else {
+            panic!("Expected component")
+        };
+        assert_eq!(
+            translatable.content,
+            TextContent::Translatable {
+                translate: "chat.type.text".to_owned(),
+                with: Some(vec![
+                    TranslatablePlaceholder::Chat(Chat::Component(ChatComponent {
+                        content: literal("b"),
+                        color: Some(ChatColor::Hex([0xFF, 0x00, 0xAA])),
+                        ..Default::default()
+                    })),
+                    TranslatablePlaceholder::Chat(Chat::Component(ChatComponent {
+                        content: literal("c"),
+                        ..Default::default()
+                    })),
+                ]),
+                fallback: None,
+            }
+        );
+        assert_eq!(
+            component.extra[2],
+            Chat::Component(ChatComponent {
+                content: literal("\n"),
+                ..Default::default()
+            })
+        );
+        // Items with data components can't be written, so their hover is left out
+        let mut item = HoverItem {
+            id: "minecraft:stone".to_owned(),
+            count: 2,
+            components: None,
+            tag: None,
+        };
+        let mut component = ChatComponent {
+            content: literal("a"),
+            hover_event: Some(HoverEvent::ShowItem(item.clone())),
+            ..Default::default()
+        };
+        assert_eq!(
+            component.to_minimessage(),
+            "<hover:show_item:'minecraft:stone':2>a"
+        );
+        item.components = Some(serde_json::json!({ "minecraft:damage": 5 }));
+        component.hover_event = Some(HoverEvent::ShowItem(item));
+        assert_eq!(component.to_minimessage(), "a");
+    }
+
+    #[test]
+    fn test_gradient() {
+        let component =
+            Chat::from_minimessage("<gradient:#000000:#ffffff>abc</gradient>").into_component();
+        let colors = component
+            .extra
+            .iter()
+            .map(|extra| extra.clone().into_component().color)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            colors,
+            vec![
+                Some(ChatColor::Hex([0x00, 0x00, 0x00])),
+                Some(ChatColor::Hex([0x80, 0x80, 0x80])),
+                Some(ChatColor::Hex([0xFF, 0xFF, 0xFF])),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lenient_and_strict() {
+        assert_eq!(
+            Chat::from_minimessage("<foo>a < b</bar>"),
+            Chat::Component(ChatComponent {
+                content: literal("<foo>a < b</bar>"),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            MiniMessage::strict().parse("ab<foo>"),
+            Err(MiniMessageError::UnknownTag {
+                tag: "foo".to_owned(),
+                offset: 2
+            })
+        );
+        assert_eq!(
+            MiniMessage::strict().parse("<red>a<bold>b</red>"),
+            Err(MiniMessageError::UnclosedTag {
+                tag: "bold".to_owned(),
+                offset: 6
+            })
+        );
+        assert_eq!(
+            MiniMessage::strict().parse("<click:nope:x>a</click>"),
+            Err(MiniMessageError::InvalidArguments {
+                tag: "click".to_owned(),
+                offset: 0
+            })
+        );
+        assert!(MiniMessage::strict().parse("<b>a</b><red>b</red>").is_ok());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let input = "<red>Hi \\<3 <bold>there</bold>!</red> <hover:show_text:'<green>it\\'s'><click:run_command:/help>help";
+        let chat = Chat::from_minimessage(input);
+        assert_eq!(
+            chat.to_minimessage(),
+            "<red>Hi \\<3 <bold>there</bold>!</red> <hover:show_text:'<green>it\\'s'><click:run_command:/help>help"
+        );
+        assert_eq!(Chat::from_minimessage(&chat.to_minimessage()), chat);
+
+        let chat = Chat::Component(ChatComponent {
+            content: TextContent::new_translatable("chat.type.text", &["Steve", "a:b"]),
+            color: Some(ChatColor::Hex([0xAA, 0xBB, 0xCC])),
+            italic: Some(false),
+            extra: vec![Chat::Component(ChatComponent {
+                content: literal("!"),
+                color: Some(ChatColor::Hex([0xAA, 0xBB, 0xCC])),
+                ..Default::default()
+            })],
+            ..Default::default()
+        });
+        assert_eq!(
+            chat.to_minimessage(),
+            "<#aabbcc><!italic><lang:chat.type.text:Steve:'a:b'>!"
+        );
+
+        // Reset colors reset everything, then restore the rest
+        let chat: Chat = serde_json::from_value(serde_json::json!({
+            "text": "a",
+            "color": "gold",
+            "bold": true,
+            "extra": [{ "text": "b", "color": "reset", "extra": [{ "text": "c", "italic": true }] }, "d"]
+        }))
+        .unwrap();
+        let minimessage = chat.to_minimessage();
+        assert_eq!(
+            minimessage,
+            "<gold><bold>a<reset><bold>b<italic>c<reset><gold><bold>d"
+        );
+        let translator = Translator::default();
+        assert_eq!(
+            Chat::from_minimessage(&minimessage).to_legacy_string(&translator),
+            chat.to_legacy_string(&translator)
+        );
+    }
+}
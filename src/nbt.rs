@@ -0,0 +1,600 @@
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    chat::{Chat, ChatComponent},
+    error::NbtError,
+    hover::HoverFormat,
+};
+
+/// Same limit as vanilla uses
+pub(crate) const MAX_DEPTH: usize = 512;
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtTag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// May contain mixed types. Those get wrapped in compounds with an empty key when written.
+    List(Vec<NbtTag>),
+    Compound(BTreeMap<String, NbtTag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+/// How the root tag is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NbtRoot {
+    /// Type, name and payload (files and network before 1.20.2)
+    Named,
+    /// Only type and payload (network since 1.20.2)
+    Nameless,
+}
+
+impl NbtTag {
+    pub fn tag_type(&self) -> u8 {
+        match self {
+            NbtTag::Byte(_) => TAG_BYTE,
+            NbtTag::Short(_) => TAG_SHORT,
+            NbtTag::Int(_) => TAG_INT,
+            NbtTag::Long(_) => TAG_LONG,
+            NbtTag::Float(_) => TAG_FLOAT,
+            NbtTag::Double(_) => TAG_DOUBLE,
+            NbtTag::ByteArray(_) => TAG_BYTE_ARRAY,
+            NbtTag::String(_) => TAG_STRING,
+            NbtTag::List(_) => TAG_LIST,
+            NbtTag::Compound(_) => TAG_COMPOUND,
+            NbtTag::IntArray(_) => TAG_INT_ARRAY,
+            NbtTag::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    /// Read a root tag. For [NbtRoot::Named], the name gets returned as well.
+    pub fn read<R: Read>(
+        reader: &mut R,
+        root: NbtRoot,
+    ) -> Result<(Option<String>, Self), NbtError> {
+        let tag_type = read_u8(reader)?;
+        if tag_type == TAG_END {
+            return Err(NbtError::UnexpectedEndTag);
+        }
+        let name = match root {
+            NbtRoot::Named => Some(read_string(reader)?),
+            NbtRoot::Nameless => None,
+        };
+        Ok((name, Self::read_payload(reader, tag_type, 0)?))
+    }
+
+    /// Write as root tag. The name is only used for [NbtRoot::Named].
+    pub fn write<W: Write>(
+        &self,
+        writer: &mut W,
+        root: NbtRoot,
+        name: &str,
+    ) -> Result<(), NbtError> {
+        writer.write_all(&[self.tag_type()])?;
+        if root == NbtRoot::Named {
+            write_string(writer, name)?;
+        }
+        self.write_payload(writer)
+    }
+
+    fn read_payload<R: Read>(reader: &mut R, tag_type: u8, depth: usize) -> Result<Self, NbtError> {
+        if depth > MAX_DEPTH {
+            return Err(NbtError::TooDeep {
+                max_depth: MAX_DEPTH,
+            });
+        }
+        Ok(match tag_type {
+            TAG_BYTE => NbtTag::Byte(read_u8(reader)? as i8),
+            TAG_SHORT => NbtTag::Short(i16::from_be_bytes(read_array(reader)?)),
+            TAG_INT => NbtTag::Int(read_i32(reader)?),
+            TAG_LONG => NbtTag::Long(i64::from_be_bytes(read_array(reader)?)),
+            TAG_FLOAT => NbtTag::Float(f32::from_be_bytes(read_array(reader)?)),
+            TAG_DOUBLE => NbtTag::Double(f64::from_be_bytes(read_array(reader)?)),
+            TAG_BYTE_ARRAY => {
+                let length = read_length(reader)?;
+                // Only allocate what is actually there, not what the length claims
+                let mut bytes = Vec::new();
+                reader.take(length as u64).read_to_end(&mut bytes)?;
+                if bytes.len() != length {
+                    return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+                }
+                NbtTag::ByteArray(bytes.into_iter().map(|byte| byte as i8).collect())
+            }
+            TAG_STRING => NbtTag::String(read_string(reader)?),
+            TAG_LIST => {
+                let element_type = read_u8(reader)?;
+                let length = read_length(reader)?;
+                if element_type == TAG_END && length > 0 {
+                    return Err(NbtError::UnexpectedEndTag);
+                }
+                let mut elements = Vec::new();
+                for _ in 0..length {
+                    elements.push(Self::read_payload(reader, element_type, depth + 1)?);
+                }
+                NbtTag::List(elements)
+            }
+            TAG_COMPOUND => {
+                let mut entries = BTreeMap::new();
+                loop {
+                    let entry_type = read_u8(reader)?;
+                    if entry_type == TAG_END {
+                        break;
+                    }
+                    let key = read_string(reader)?;
+                    entries.insert(key, Self::read_payload(reader, entry_type, depth + 1)?);
+                }
+                NbtTag::Compound(entries)
+            }
+            TAG_INT_ARRAY => {
+                let length = read_length(reader)?;
+                let mut ints = Vec::new();
+                for _ in 0..length {
+                    ints.push(read_i32(reader)?);
+                }
+                NbtTag::IntArray(ints)
+            }
+            TAG_LONG_ARRAY => {
+                let length = read_length(reader)?;
+                let mut longs = Vec::new();
+                for _ in 0..length {
+                    longs.push(i64::from_be_bytes(read_array(reader)?));
+                }
+                NbtTag::LongArray(longs)
+            }
+            TAG_END => return Err(NbtError::UnexpectedEndTag),
+            _ => return Err(NbtError::InvalidTagType { tag_type }),
+        })
+    }
+
+    fn write_payload<W: Write>(&self, writer: &mut W) -> Result<(), NbtError> {
+        match self {
+            NbtTag::Byte(value) => writer.write_all(&value.to_be_bytes())?,
+            NbtTag::Short(value) => writer.write_all(&value.to_be_bytes())?,
+            NbtTag::Int(value) => writer.write_all(&value.to_be_bytes())?,
+            NbtTag::Long(value) => writer.write_all(&value.to_be_bytes())?,
+            NbtTag::Float(value) => writer.write_all(&value.to_be_bytes())?,
+            NbtTag::Double(value) => writer.write_all(&value.to_be_bytes())?,
+            NbtTag::ByteArray(bytes) => {
+                write_length(writer, bytes.len())?;
+                let bytes = bytes.iter().map(|byte| *byte as u8).collect::<Vec<_>>();
+                writer.write_all(&bytes)?;
+            }
+            NbtTag::String(value) => write_string(writer, value)?,
+            NbtTag::List(elements) => {
+                let element_type = elements.first().map(|tag| tag.tag_type());
+                let homogeneous = elements
+                    .iter()
+                    .all(|element| Some(element.tag_type()) == element_type);
+                if homogeneous {
+                    writer.write_all(&[element_type.unwrap_or(TAG_END)])?;
+                    write_length(writer, elements.len())?;
+                    for element in elements {
+                        element.write_payload(writer)?;
+                    }
+                } else {
+                    // Mixed lists are written as list of compounds, where
+                    // anything that isn't one gets wrapped as { "": <value> }
+                    writer.write_all(&[TAG_COMPOUND])?;
+                    write_length(writer, elements.len())?;
+                    for element in elements {
+                        match element {
+                            NbtTag::Compound(entries) if !is_wrapper(entries) => {
+                                element.write_payload(writer)?
+                            }
+                            _ => {
+                                NbtTag::Compound(BTreeMap::from([(String::new(), element.clone())]))
+                                    .write_payload(writer)?
+                            }
+                        }
+                    }
+                }
+            }
+            NbtTag::Compound(entries) => {
+                for (key, value) in entries {
+                    writer.write_all(&[value.tag_type()])?;
+                    write_string(writer, key)?;
+                    value.write_payload(writer)?;
+                }
+                writer.write_all(&[TAG_END])?;
+            }
+            NbtTag::IntArray(ints) => {
+                write_length(writer, ints.len())?;
+                for int in ints {
+                    writer.write_all(&int.to_be_bytes())?;
+                }
+            }
+            NbtTag::LongArray(longs) => {
+                write_length(writer, longs.len())?;
+                for long in longs {
+                    writer.write_all(&long.to_be_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert to the equivalent json. Wrapped elements of mixed lists get unwrapped.
+    pub fn to_json_value(&self) -> Result<serde_json::Value, NbtError> {
+        use serde_json::Value;
+
+        let float = |value: f64| {
+            serde_json::Number::from_f64(value)
+                .map(Value::Number)
+                .ok_or_else(|| NbtError::UnrepresentableValue {
+                    value: value.to_string(),
+                })
+        };
+        Ok(match self {
+            NbtTag::Byte(value) => Value::from(*value),
+            NbtTag::Short(value) => Value::from(*value),
+            NbtTag::Int(value) => Value::from(*value),
+            NbtTag::Long(value) => Value::from(*value),
+            NbtTag::Float(value) => float(*value as f64)?,
+            NbtTag::Double(value) => float(*value)?,
+            NbtTag::ByteArray(bytes) => Value::from(bytes.clone()),
+            NbtTag::String(value) => Value::from(value.as_str()),
+            NbtTag::List(elements) => Value::Array(
+                elements
+                    .iter()
+                    .map(|element| match element {
+                        NbtTag::Compound(entries) if is_wrapper(entries) => {
+                            entries[""].to_json_value()
+                        }
+                        _ => element.to_json_value(),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            NbtTag::Compound(entries) => Value::Object(
+                entries
+                    .iter()
+                    .map(|(key, value)| Ok((key.to_owned(), value.to_json_value()?)))
+                    .collect::<Result<_, NbtError>>()?,
+            ),
+            NbtTag::IntArray(ints) => Value::from(ints.clone()),
+            NbtTag::LongArray(longs) => Value::from(longs.clone()),
+        })
+    }
+
+    /// Convert json to NBT the way vanilla encodes text components:
+    /// booleans become bytes, null fields get omitted and components with nothing but text
+    /// become plain strings.
+    pub fn from_json_value(value: &serde_json::Value) -> Result<Self, NbtError> {
+        use serde_json::Value;
+
+        Ok(match value {
+            Value::Null => {
+                return Err(NbtError::UnrepresentableValue {
+                    value: "null".to_owned(),
+                })
+            }
+            Value::Bool(value) => NbtTag::Byte(*value as i8),
+            Value::Number(number) => {
+                if let Some(value) = number.as_i64() {
+                    match i32::try_from(value) {
+                        Ok(value) => NbtTag::Int(value),
+                        Err(_) => NbtTag::Long(value),
+                    }
+                } else if let Some(value) = number.as_f64() {
+                    NbtTag::Double(value)
+                } else {
+                    return Err(NbtError::UnrepresentableValue {
+                        value: number.to_string(),
+                    });
+                }
+            }
+            Value::String(value) => NbtTag::String(value.to_owned()),
+            Value::Array(elements) => NbtTag::List(
+                elements
+                    .iter()
+                    .map(Self::from_json_value)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Object(entries) => {
+                // Absent optional fields are serialized as null, which NBT has no equivalent for
+                let entries = entries
+                    .iter()
+                    .filter(|(_, value)| !value.is_null())
+                    .collect::<Vec<_>>();
+                match entries.as_slice() {
+                    [(key, Value::String(text))] if key.as_str() == "text" => {
+                        NbtTag::String(text.to_owned())
+                    }
+                    _ => NbtTag::Compound(
+                        entries
+                            .into_iter()
+                            .map(|(key, value)| Ok((key.to_owned(), Self::from_json_value(value)?)))
+                            .collect::<Result<_, NbtError>>()?,
+                    ),
+                }
+            }
+        })
+    }
+}
+
+fn is_wrapper(entries: &BTreeMap<String, NbtTag>) -> bool {
+    entries.len() == 1 && entries.contains_key("")
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> Result<[u8; N], NbtError> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8, NbtError> {
+    Ok(read_array::<R, 1>(reader)?[0])
+}
+
+fn read_i32<R: Read>(reader: &mut R) -> Result<i32, NbtError> {
+    Ok(i32::from_be_bytes(read_array(reader)?))
+}
+
+fn read_length<R: Read>(reader: &mut R) -> Result<usize, NbtError> {
+    let length = read_i32(reader)?;
+    usize::try_from(length).map_err(|_| NbtError::NegativeLength { length })
+}
+
+fn write_length<W: Write>(writer: &mut W, length: usize) -> Result<(), NbtError> {
+    let length = i32::try_from(length).map_err(|_| NbtError::UnrepresentableValue {
+        value: format!("Length of {length}"),
+    })?;
+    writer.write_all(&length.to_be_bytes())?;
+    Ok(())
+}
+
+/// Strings are encoded in javas "modified UTF-8" (CESU-8 with a two byte null)
+fn read_string<R: Read>(reader: &mut R) -> Result<String, NbtError> {
+    let length = u16::from_be_bytes(read_array(reader)?) as usize;
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes)?;
+
+    let mut units = Vec::with_capacity(length);
+    let mut i = 0;
+    while i < bytes.len() {
+        let continuation = |offset: usize| match bytes.get(i + offset) {
+            Some(byte) if byte & 0xC0 == 0x80 => Ok((byte & 0x3F) as u16),
+            _ => Err(NbtError::InvalidString),
+        };
+        let byte = bytes[i];
+        if byte & 0x80 == 0 {
+            units.push(byte as u16);
+            i += 1;
+        } else if byte & 0xE0 == 0xC0 {
+            units.push(((byte & 0x1F) as u16) << 6 | continuation(1)?);
+            i += 2;
+        } else if byte & 0xF0 == 0xE0 {
+            units.push(((byte & 0x0F) as u16) << 12 | continuation(1)? << 6 | continuation(2)?);
+            i += 3;
+        } else {
+            return Err(NbtError::InvalidString);
+        }
+    }
+    String::from_utf16(&units).map_err(|_| NbtError::InvalidString)
+}
+
+fn write_string<W: Write>(writer: &mut W, string: &str) -> Result<(), NbtError> {
+    let mut bytes = Vec::with_capacity(string.len());
+    for unit in string.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    let length = u16::try_from(bytes.len()).map_err(|_| NbtError::StringTooLong {
+        length: bytes.len(),
+    })?;
+    writer.write_all(&length.to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+fn from_nbt<T: DeserializeOwned>(tag: &NbtTag) -> Result<T, NbtError> {
+    Ok(serde_json::from_value(tag.to_json_value()?)?)
+}
+
+fn to_nbt<T: Serialize>(value: &T) -> Result<NbtTag, NbtError> {
+    NbtTag::from_json_value(&serde_json::to_value(value)?)
+}
+
+impl Chat {
+    pub fn from_nbt(tag: &NbtTag) -> Result<Self, NbtError> {
+        from_nbt(tag)
+    }
+
+    /// Events are written as `clickEvent` and `hoverEvent` (1.20.3 to 1.21.4).
+    /// See [Chat::to_nbt_with] for newer versions.
+    pub fn to_nbt(&self) -> Result<NbtTag, NbtError> {
+        to_nbt(self)
+    }
+
+    /// Like [Chat::to_nbt], but writing events in the format of the targeted version
+    pub fn to_nbt_with(&self, format: HoverFormat) -> Result<NbtTag, NbtError> {
+        NbtTag::from_json_value(&self.to_json_value_with(format)?)
+    }
+
+    pub fn read_nbt<R: Read>(reader: &mut R, root: NbtRoot) -> Result<Self, NbtError> {
+        Self::from_nbt(&NbtTag::read(reader, root)?.1)
+    }
+
+    pub fn write_nbt<W: Write>(&self, writer: &mut W, root: NbtRoot) -> Result<(), NbtError> {
+        self.to_nbt()?.write(writer, root, "")
+    }
+}
+
+impl ChatComponent {
+    /// Also accepts plain strings and lists, as vanilla does
+    pub fn from_nbt(tag: &NbtTag) -> Result<Self, NbtError> {
+        Ok(Chat::from_nbt(tag)?.into_component())
+    }
+
+    /// Events are written as `clickEvent` and `hoverEvent` (1.20.3 to 1.21.4).
+    /// See [ChatComponent::to_nbt_with] for newer versions.
+    pub fn to_nbt(&self) -> Result<NbtTag, NbtError> {
+        to_nbt(self)
+    }
+
+    /// Like [ChatComponent::to_nbt], but writing events in the format of the targeted version
+    pub fn to_nbt_with(&self, format: HoverFormat) -> Result<NbtTag, NbtError> {
+        NbtTag::from_json_value(&self.to_json_value_with(format)?)
+    }
+
+    pub fn read_nbt<R: Read>(reader: &mut R, root: NbtRoot) -> Result<Self, NbtError> {
+        Self::from_nbt(&NbtTag::read(reader, root)?.1)
+    }
+
+    pub fn write_nbt<W: Write>(&self, writer: &mut W, root: NbtRoot) -> Result<(), NbtError> {
+        self.to_nbt()?.write(writer, root, "")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat::{TextContent, TextFormatter},
+        formatting::ChatColor,
+    };
+
+    #[test]
+    fn test_nameless_string_root() {
+        // Literal only text is sent as plain string tag
+        let chat = Chat::Component(ChatComponent {
+            content: TextContent::new_literal("Hi\0✓😀"),
+            ..Default::default()
+        });
+        let mut bytes = Vec::new();
+        chat.write_nbt(&mut bytes, NbtRoot::Nameless).unwrap();
+        assert_eq!(bytes[0], TAG_STRING);
+        assert_eq!(
+            Chat::read_nbt(&mut bytes.as_slice(), NbtRoot::Nameless).unwrap(),
+            Chat::Text("Hi\0✓😀".to_owned())
+        );
+
+        // "§" in literal text stays literal after the round trip
+        let translator = crate::translator::Translator::default();
+        let chat: Chat = serde_json::from_str(r#"{"text": "Price: 5§a"}"#).unwrap();
+        let mut bytes = Vec::new();
+        chat.write_nbt(&mut bytes, NbtRoot::Nameless).unwrap();
+        let read = Chat::read_nbt(&mut bytes.as_slice(), NbtRoot::Nameless).unwrap();
+        assert_eq!(read.to_plain_string(&translator), "Price: 5§a");
+        assert_eq!(
+            read.to_legacy_string(&translator),
+            chat.to_legacy_string(&translator)
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let chat: Chat = serde_json::from_value(serde_json::json!({
+            "text": "Hello ",
+            "color": "red",
+            "bold": true,
+            "extra": [
+                "plain",
+                { "translate": "test.key", "with": ["Arg", 5], "italic": true },
+                { "text": "", "extra": [ { "keybind": "key.jump" } ] }
+            ]
+        }))
+        .unwrap();
+
+        for root in [NbtRoot::Named, NbtRoot::Nameless] {
+            let mut bytes = Vec::new();
+            chat.write_nbt(&mut bytes, root).unwrap();
+            assert_eq!(Chat::read_nbt(&mut bytes.as_slice(), root).unwrap(), chat);
+        }
+    }
+
+    #[test]
+    fn test_mixed_list_and_bytes() {
+        let tag = NbtTag::Compound(BTreeMap::from([
+            ("text".to_owned(), NbtTag::String("a".to_owned())),
+            ("italic".to_owned(), NbtTag::Byte(1)),
+            ("color".to_owned(), NbtTag::String("gold".to_owned())),
+            (
+                "extra".to_owned(),
+                NbtTag::List(vec![
+                    NbtTag::String("b".to_owned()),
+                    NbtTag::Compound(BTreeMap::from([(
+                        "text".to_owned(),
+                        NbtTag::String("c".to_owned()),
+                    )])),
+                ]),
+            ),
+        ]));
+        let mut bytes = Vec::new();
+        tag.write(&mut bytes, NbtRoot::Named, "").unwrap();
+        let (name, read_tag) = NbtTag::read(&mut bytes.as_slice(), NbtRoot::Named).unwrap();
+        assert_eq!(name.as_deref(), Some(""));
+        // The string got wrapped as { "": "b" }
+        let NbtTag::Compound(entries) = &read_tag else {
+            panic!("Expected compound");
+        };
+        assert_eq!(
+            entries["extra"],
+            NbtTag::List(vec![
+                NbtTag::Compound(BTreeMap::from([(
+                    String::new(),
+                    NbtTag::String("b".to_owned())
+                )])),
+                NbtTag::Compound(BTreeMap::from([(
+                    "text".to_owned(),
+                    NbtTag::String("c".to_owned())
+                )])),
+            ])
+        );
+
+        let component = ChatComponent::from_nbt(&read_tag).unwrap();
+        assert_eq!(component.italic, Some(true));
+        assert_eq!(component.color, Some(ChatColor::Gold));
+        assert_eq!(component.extra[0], Chat::Text("b".to_owned()));
+    }
+
+    #[test]
+    fn test_huge_length() {
+        for tag_type in [TAG_BYTE_ARRAY, TAG_INT_ARRAY, TAG_LONG_ARRAY] {
+            let bytes = [tag_type, 0x7F, 0xFF, 0xFF, 0xFF, 1, 2];
+            assert!(matches!(
+                NbtTag::read(&mut bytes.as_slice(), NbtRoot::Nameless),
+                Err(NbtError::Io(_))
+            ));
+        }
+        let list = [TAG_LIST, TAG_COMPOUND, 0x7F, 0xFF, 0xFF, 0xFF, TAG_END];
+        assert!(matches!(
+            NbtTag::read(&mut list.as_slice(), NbtRoot::Nameless),
+            Err(NbtError::Io(_))
+        ));
+    }
+}
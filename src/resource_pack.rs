@@ -0,0 +1,237 @@
+use std::{
+    collections::BTreeMap,
+    io::{Read, Seek},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::ResourcePackError, locale::normalize_locale, translator::Translator};
+
+/// A `language` entry of pack.mcmeta
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LanguageInfo {
+    pub name: String,
+    pub region: String,
+    #[serde(default)]
+    pub bidirectional: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PackMcmeta {
+    #[serde(default)]
+    language: BTreeMap<String, LanguageInfo>,
+}
+
+/// A lang file found in a pack
+struct LangFile {
+    namespace: String,
+    locale: String,
+    is_json: bool,
+    path: String,
+    content: String,
+}
+
+/// The translations of a resource pack, with the lang files of all namespaces merged
+#[derive(Debug, Clone, Default)]
+pub struct ResourcePack {
+    /// Locales the pack declares in pack.mcmeta
+    pub languages: BTreeMap<String, LanguageInfo>,
+    /// Translations by normalized locale
+    pub translations: BTreeMap<String, Translator>,
+}
+
+/// Split assets/<namespace>/lang/<locale>.<json|lang> into namespace, locale and whether it's json
+fn lang_file_path(path: &str) -> Option<(&str, &str, bool)> {
+    let ["assets", namespace, "lang", file_name] = path.split('/').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let (locale, extension) = file_name.rsplit_once('.')?;
+    match extension {
+        "json" => Some((namespace, locale, true)),
+        "lang" => Some((namespace, locale, false)),
+        _ => None,
+    }
+}
+
+impl ResourcePack {
+    pub fn from_zip(path: impl AsRef<Path>) -> Result<Self, ResourcePackError> {
+        Self::from_zip_reader(std::fs::File::open(path)?)
+    }
+
+    pub fn from_zip_reader<R: Read + Seek>(reader: R) -> Result<Self, ResourcePackError> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let mut mcmeta = None;
+        let mut lang_files = Vec::new();
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+            let path = file.name().to_owned();
+            let lang_file = lang_file_path(&path).map(|(namespace, locale, is_json)| {
+                (namespace.to_owned(), locale.to_owned(), is_json)
+            });
+            if lang_file.is_none() && path != "pack.mcmeta" {
+                continue;
+            }
+            let mut content = String::new();
+            file.read_to_string(&mut content)?;
+            match lang_file {
+                Some((namespace, locale, is_json)) => lang_files.push(LangFile {
+                    namespace,
+                    locale,
+                    is_json,
+                    path,
+                    content,
+                }),
+                None => mcmeta = Some(content),
+            }
+        }
+        Self::from_files(mcmeta.as_deref(), lang_files)
+    }
+
+    pub fn from_directory(path: impl AsRef<Path>) -> Result<Self, ResourcePackError> {
+        let root = path.as_ref();
+        let mcmeta = match std::fs::read_to_string(root.join("pack.mcmeta")) {
+            Ok(mcmeta) => Some(mcmeta),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+        let mut lang_files = Vec::new();
+        let assets = root.join("assets");
+        if assets.is_dir() {
+            for namespace in std::fs::read_dir(&assets)? {
+                let lang = namespace?.path().join("lang");
+                if !lang.is_dir() {
+                    continue;
+                }
+                for file in std::fs::read_dir(lang)? {
+                    let file = file?.path();
+                    let Some(path) = file
+                        .strip_prefix(root)
+                        .ok()
+                        .and_then(|path| path.to_str())
+                        .map(|path| path.replace(std::path::MAIN_SEPARATOR, "/"))
+                    else {
+                        continue;
+                    };
+                    if let Some((namespace, locale, is_json)) = lang_file_path(&path) {
+                        lang_files.push(LangFile {
+                            namespace: namespace.to_owned(),
+                            locale: locale.to_owned(),
+                            is_json,
+                            content: std::fs::read_to_string(&file)?,
+                            path,
+                        });
+                    }
+                }
+            }
+        }
+        Self::from_files(mcmeta.as_deref(), lang_files)
+    }
+
+    fn from_files(
+        mcmeta: Option<&str>,
+        mut lang_files: Vec<LangFile>,
+    ) -> Result<Self, ResourcePackError> {
+        let mcmeta: PackMcmeta =
+            match mcmeta {
+                Some(mcmeta) => serde_json::from_str(mcmeta.trim_start_matches('\u{feff}'))
+                    .map_err(|source| ResourcePackError::InvalidJson {
+                        path: "pack.mcmeta".to_owned(),
+                        source,
+                    })?,
+                None => PackMcmeta::default(),
+            };
+        let mut pack = ResourcePack {
+            languages: mcmeta
+                .language
+                .into_iter()
+                .map(|(locale, info)| (normalize_locale(&locale), info))
+                .collect(),
+            ..Default::default()
+        };
+
+        // Namespaces in a fixed order, json winning over a .lang file of the same locale
+        lang_files.sort_by(|a, b| (&a.namespace, a.is_json).cmp(&(&b.namespace, b.is_json)));
+        for file in lang_files {
+            let content = file.content.trim_start_matches('\u{feff}');
+            let translator = if file.is_json {
+                Translator::from_translation_content(content).map_err(|source| {
+                    ResourcePackError::InvalidJson {
+                        path: file.path,
+                        source,
+                    }
+                })?
+            } else {
+                Translator::from_lang_content(content)
+            };
+            pack.translations
+                .entry(normalize_locale(&file.locale))
+                .or_default()
+                .overlay(&translator);
+        }
+        Ok(pack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use super::*;
+    use crate::locale::TranslatorSet;
+
+    fn zip(files: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in files {
+            writer
+                .start_file(*path, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_stacked_packs() {
+        let base = ResourcePack::from_zip_reader(zip(&[
+            (
+                "pack.mcmeta",
+                r#"{"pack": {"pack_format": 15, "description": ""},
+                    "language": {"x_ar": {"name": "Arabic-ish", "region": "Test", "bidirectional": true}}}"#,
+            ),
+            ("assets/minecraft/lang/en_us.json", r#"{"a": "Base A", "b": "Base B"}"#),
+            ("assets/myplugin/lang/en_US.json", r#"{"plugin.hello": "Hello"}"#),
+            ("assets/myplugin/lang/x_ar.lang", "plugin.hello=\\u0645\n"),
+            ("assets/myplugin/textures/a.png", ""),
+        ]))
+        .unwrap();
+        assert_eq!(
+            base.languages["x_ar"],
+            LanguageInfo {
+                name: "Arabic-ish".to_owned(),
+                region: "Test".to_owned(),
+                bidirectional: true,
+            }
+        );
+        assert_eq!(
+            base.translations["en_us"].get("plugin.hello"),
+            Some("Hello")
+        );
+
+        let directory =
+            std::env::temp_dir().join(format!("chat-formatting-pack-{}", std::process::id()));
+        let lang = directory.join("assets").join("minecraft").join("lang");
+        std::fs::create_dir_all(&lang).unwrap();
+        std::fs::write(lang.join("en_us.json"), r#"{"b": "Top B"}"#).unwrap();
+        let top = ResourcePack::from_directory(&directory).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let mut set = TranslatorSet::new();
+        set.add_resource_pack(&base).unwrap();
+        set.add_resource_pack(&top).unwrap();
+        assert_eq!(set.get("en_us", "a").unwrap(), Some("Base A"));
+        assert_eq!(set.get("en_us", "b").unwrap(), Some("Top B"));
+        assert_eq!(set.get("x_ar", "plugin.hello").unwrap(), Some("م"));
+        assert!(set.language_info("x_ar").unwrap().bidirectional);
+    }
+}
@@ -0,0 +1,613 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    chat::{Chat, ChatComponent},
+    error::{NbtError, SnbtError},
+    hover::HoverFormat,
+    nbt::{NbtTag, MAX_DEPTH},
+};
+
+struct Parser<'a> {
+    input: &'a str,
+    chars: Vec<(usize, char)>,
+    pos: usize,
+    depth: usize,
+}
+
+fn is_unquoted_char(chr: char) -> bool {
+    chr.is_ascii_alphanumeric() || matches!(chr, '_' | '-' | '.' | '+')
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            chars: input.char_indices().collect(),
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    /// Line and column (both starting at 1) of the char at the given index
+    fn position(&self, pos: usize) -> (usize, usize) {
+        let byte_offset = self
+            .chars
+            .get(pos)
+            .map(|(offset, _)| *offset)
+            .unwrap_or(self.input.len());
+        let before = &self.input[..byte_offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|chr| *chr != '\n').count() + 1;
+        (line, column)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|(_, chr)| *chr)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str) -> SnbtError {
+        let (line, column) = self.position(self.pos);
+        match self.peek() {
+            Some(found) => SnbtError::UnexpectedChar {
+                expected,
+                found,
+                line,
+                column,
+            },
+            None => SnbtError::UnexpectedEnd { line, column },
+        }
+    }
+
+    fn expect(&mut self, expected_chr: char, expected: &'static str) -> Result<(), SnbtError> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected_chr) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn parse_root(&mut self) -> Result<NbtTag, SnbtError> {
+        let tag = self.parse_value()?;
+        self.skip_whitespace();
+        if self.pos < self.chars.len() {
+            let (line, column) = self.position(self.pos);
+            return Err(SnbtError::TrailingData { line, column });
+        }
+        Ok(tag)
+    }
+
+    fn parse_value(&mut self) -> Result<NbtTag, SnbtError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_nested(Self::parse_compound),
+            Some('[') => self.parse_nested(Self::parse_list_or_array),
+            Some('"' | '\'') => Ok(NbtTag::String(self.parse_quoted()?)),
+            Some(chr) if is_unquoted_char(chr) => {
+                let token = self.parse_unquoted();
+                Ok(Self::interpret_unquoted(&token))
+            }
+            _ => Err(self.unexpected("value")),
+        }
+    }
+
+    /// Parse a compound or list one level deeper, failing instead of overflowing the stack
+    fn parse_nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<NbtTag, SnbtError>,
+    ) -> Result<NbtTag, SnbtError> {
+        if self.depth >= MAX_DEPTH {
+            let (line, column) = self.position(self.pos);
+            return Err(SnbtError::TooDeep {
+                max_depth: MAX_DEPTH,
+                line,
+                column,
+            });
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_compound(&mut self) -> Result<NbtTag, SnbtError> {
+        self.expect('{', "'{'")?;
+        let mut entries = BTreeMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(NbtTag::Compound(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = match self.peek() {
+                Some('"' | '\'') => self.parse_quoted()?,
+                Some(chr) if is_unquoted_char(chr) => self.parse_unquoted(),
+                _ => return Err(self.unexpected("key")),
+            };
+            self.expect(':', "':'")?;
+            entries.insert(key, self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(NbtTag::Compound(entries));
+                }
+                _ => return Err(self.unexpected("',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_list_or_array(&mut self) -> Result<NbtTag, SnbtError> {
+        self.expect('[', "'['")?;
+        let array_type = match (self.peek(), self.chars.get(self.pos + 1).map(|(_, c)| *c)) {
+            (Some(chr @ ('B' | 'I' | 'L')), Some(';')) => {
+                self.pos += 2;
+                Some(chr)
+            }
+            _ => None,
+        };
+
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let element_pos = self.pos;
+                let element = self.parse_value()?;
+                if let Some(array_type) = array_type {
+                    let is_integer = matches!(
+                        element,
+                        NbtTag::Byte(_) | NbtTag::Short(_) | NbtTag::Int(_) | NbtTag::Long(_)
+                    );
+                    if !is_integer {
+                        let (line, column) = self.position(element_pos);
+                        return Err(SnbtError::InvalidArrayElement {
+                            array_type,
+                            line,
+                            column,
+                        });
+                    }
+                }
+                elements.push((element_pos, element));
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(',') => self.pos += 1,
+                    Some(']') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.unexpected("',' or ']'")),
+                }
+            }
+        }
+
+        let Some(array_type) = array_type else {
+            return Ok(NbtTag::List(
+                elements.into_iter().map(|(_, element)| element).collect(),
+            ));
+        };
+        let mut values = Vec::with_capacity(elements.len());
+        for (element_pos, element) in elements {
+            let value = match element {
+                NbtTag::Byte(value) => value as i64,
+                NbtTag::Short(value) => value as i64,
+                NbtTag::Int(value) => value as i64,
+                NbtTag::Long(value) => value,
+                _ => unreachable!("Checked while parsing"),
+            };
+            let fits = match array_type {
+                'B' => i8::try_from(value).is_ok(),
+                'I' => i32::try_from(value).is_ok(),
+                _ => true,
+            };
+            if !fits {
+                let (line, column) = self.position(element_pos);
+                return Err(SnbtError::InvalidArrayElement {
+                    array_type,
+                    line,
+                    column,
+                });
+            }
+            values.push(value);
+        }
+        Ok(match array_type {
+            'B' => NbtTag::ByteArray(values.into_iter().map(|value| value as i8).collect()),
+            'I' => NbtTag::IntArray(values.into_iter().map(|value| value as i32).collect()),
+            _ => NbtTag::LongArray(values),
+        })
+    }
+
+    fn parse_unquoted(&mut self) -> String {
+        let mut token = String::new();
+        while let Some(chr) = self.peek().filter(|chr| is_unquoted_char(*chr)) {
+            token.push(chr);
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn parse_quoted(&mut self) -> Result<String, SnbtError> {
+        let quote = self.peek().ok_or_else(|| self.unexpected("quote"))?;
+        self.pos += 1;
+        let mut string = String::new();
+        loop {
+            let Some(chr) = self.peek() else {
+                return Err(self.unexpected("closing quote"));
+            };
+            self.pos += 1;
+            if chr == quote {
+                return Ok(string);
+            }
+            if chr != '\\' {
+                string.push(chr);
+                continue;
+            }
+
+            let escape_pos = self.pos - 1;
+            let Some(escaped) = self.peek() else {
+                return Err(self.unexpected("escape sequence"));
+            };
+            self.pos += 1;
+            let hex_digits = match escaped {
+                '\\' | '\'' | '"' => {
+                    string.push(escaped);
+                    continue;
+                }
+                'b' => {
+                    string.push('\u{8}');
+                    continue;
+                }
+                'f' => {
+                    string.push('\u{C}');
+                    continue;
+                }
+                'n' => {
+                    string.push('\n');
+                    continue;
+                }
+                'r' => {
+                    string.push('\r');
+                    continue;
+                }
+                's' => {
+                    string.push(' ');
+                    continue;
+                }
+                't' => {
+                    string.push('\t');
+                    continue;
+                }
+                'x' => 2,
+                'u' => 4,
+                'U' => 8,
+                _ => 0,
+            };
+            let digits = self
+                .chars
+                .get(self.pos..self.pos + hex_digits)
+                .map(|chars| chars.iter().map(|(_, chr)| *chr).collect::<String>());
+            let decoded = digits
+                .as_ref()
+                .filter(|_| hex_digits > 0)
+                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                .and_then(char::from_u32);
+            match decoded {
+                Some(decoded) => {
+                    string.push(decoded);
+                    self.pos += hex_digits;
+                }
+                None => {
+                    let (line, column) = self.position(escape_pos);
+                    return Err(SnbtError::InvalidEscape {
+                        sequence: format!("\\{}{}", escaped, digits.unwrap_or_default()),
+                        line,
+                        column,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Unquoted tokens are numbers or booleans if they look like one and strings otherwise
+    /// Like vanilla, tokens that aren't valid numbers (like 1.2.3 or 1ef) are strings
+    fn interpret_unquoted(token: &str) -> NbtTag {
+        match token {
+            "true" => return NbtTag::Byte(1),
+            "false" => return NbtTag::Byte(0),
+            _ => {}
+        }
+
+        let looks_numeric = token
+            .trim_start_matches(['+', '-'])
+            .starts_with(|chr: char| chr.is_ascii_digit() || chr == '.');
+        if !looks_numeric {
+            return NbtTag::String(token.to_owned());
+        }
+
+        let (number, suffix) = match token.chars().last() {
+            Some(chr @ ('b' | 'B' | 's' | 'S' | 'l' | 'L' | 'f' | 'F' | 'd' | 'D')) => {
+                (&token[..token.len() - 1], Some(chr.to_ascii_lowercase()))
+            }
+            _ => (token, None),
+        };
+        let is_decimal = number.contains(['.', 'e', 'E']);
+        let number = match suffix {
+            Some('b') => number.parse().map(NbtTag::Byte).ok(),
+            Some('s') => number.parse().map(NbtTag::Short).ok(),
+            Some('l') => number.parse().map(NbtTag::Long).ok(),
+            Some('f') => number.parse().map(NbtTag::Float).ok(),
+            Some('d') => number.parse().map(NbtTag::Double).ok(),
+            _ if is_decimal => number.parse().map(NbtTag::Double).ok(),
+            _ => number.parse().map(NbtTag::Int).ok(),
+        };
+        number.unwrap_or_else(|| NbtTag::String(token.to_owned()))
+    }
+}
+
+fn write_quoted(f: &mut std::fmt::Formatter<'_>, string: &str) -> std::fmt::Result {
+    // Same as vanilla: Use double quotes unless the first quote found inside is one
+    let quote = match string.chars().find(|chr| *chr == '"' || *chr == '\'') {
+        Some('"') => '\'',
+        _ => '"',
+    };
+    write!(f, "{}", quote)?;
+    for chr in string.chars() {
+        match chr {
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            _ if chr == quote => write!(f, "\\{}", chr)?,
+            _ if chr.is_control() => write!(f, "\\u{:04x}", chr as u32)?,
+            _ => write!(f, "{}", chr)?,
+        }
+    }
+    write!(f, "{}", quote)
+}
+
+/// Prints the tag as SNBT
+impl std::fmt::Display for NbtTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn write_array<T: std::fmt::Display>(
+            f: &mut std::fmt::Formatter<'_>,
+            prefix: char,
+            suffix: &str,
+            values: &[T],
+        ) -> std::fmt::Result {
+            write!(f, "[{};", prefix)?;
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}{}", value, suffix)?;
+            }
+            write!(f, "]")
+        }
+
+        match self {
+            NbtTag::Byte(value) => write!(f, "{}b", value),
+            NbtTag::Short(value) => write!(f, "{}s", value),
+            NbtTag::Int(value) => write!(f, "{}", value),
+            NbtTag::Long(value) => write!(f, "{}L", value),
+            NbtTag::Float(value) => write!(f, "{:?}f", value),
+            NbtTag::Double(value) => write!(f, "{:?}d", value),
+            NbtTag::ByteArray(values) => write_array(f, 'B', "b", values),
+            NbtTag::String(value) => write_quoted(f, value),
+            NbtTag::List(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            NbtTag::Compound(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    if !key.is_empty() && key.chars().all(is_unquoted_char) {
+                        write!(f, "{}", key)?;
+                    } else {
+                        write_quoted(f, key)?;
+                    }
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+            NbtTag::IntArray(values) => write_array(f, 'I', "", values),
+            NbtTag::LongArray(values) => write_array(f, 'L', "L", values),
+        }
+    }
+}
+
+impl NbtTag {
+    pub fn from_snbt(snbt: &str) -> Result<Self, SnbtError> {
+        Parser::new(snbt).parse_root()
+    }
+
+    pub fn to_snbt(&self) -> String {
+        self.to_string()
+    }
+}
+
+fn invalid_component(err: NbtError) -> SnbtError {
+    SnbtError::InvalidComponent {
+        message: match err {
+            NbtError::InvalidComponent(err) => err.to_string(),
+            err => err.to_string(),
+        },
+    }
+}
+
+impl Chat {
+    pub fn from_snbt(snbt: &str) -> Result<Self, SnbtError> {
+        Self::from_nbt(&NbtTag::from_snbt(snbt)?).map_err(invalid_component)
+    }
+
+    pub fn to_snbt(&self) -> Result<String, NbtError> {
+        Ok(self.to_nbt()?.to_snbt())
+    }
+
+    /// Like [Self::to_snbt], but writing events in the format of the targeted version
+    pub fn to_snbt_with(&self, format: HoverFormat) -> Result<String, NbtError> {
+        Ok(self.to_nbt_with(format)?.to_snbt())
+    }
+}
+
+impl ChatComponent {
+    pub fn from_snbt(snbt: &str) -> Result<Self, SnbtError> {
+        Self::from_nbt(&NbtTag::from_snbt(snbt)?).map_err(invalid_component)
+    }
+
+    pub fn to_snbt(&self) -> Result<String, NbtError> {
+        Ok(self.to_nbt()?.to_snbt())
+    }
+
+    /// Like [Self::to_snbt], but writing events in the format of the targeted version
+    pub fn to_snbt_with(&self, format: HoverFormat) -> Result<String, NbtError> {
+        Ok(self.to_nbt_with(format)?.to_snbt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chat::{ClickAction, TextContent},
+        formatting::ChatColor,
+    };
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(
+            NbtTag::from_snbt("{a: 1b, 'b c': -2s, d:3L, e:1.5f, f:2.0, g:+4, h:true, i:1.2.3}"),
+            Ok(NbtTag::Compound(BTreeMap::from([
+                ("a".to_owned(), NbtTag::Byte(1)),
+                ("b c".to_owned(), NbtTag::Short(-2)),
+                ("d".to_owned(), NbtTag::Long(3)),
+                ("e".to_owned(), NbtTag::Float(1.5)),
+                ("f".to_owned(), NbtTag::Double(2.0)),
+                ("g".to_owned(), NbtTag::Int(4)),
+                ("h".to_owned(), NbtTag::Byte(1)),
+                ("i".to_owned(), NbtTag::String("1.2.3".to_owned())),
+            ])))
+        );
+        assert_eq!(
+            NbtTag::from_snbt(r#"["a\"b", 'it\'s', "§a"]"#),
+            Ok(NbtTag::List(vec![
+                NbtTag::String("a\"b".to_owned()),
+                NbtTag::String("it's".to_owned()),
+                NbtTag::String("§a".to_owned()),
+            ]))
+        );
+        assert_eq!(
+            NbtTag::from_snbt("[I; 1, 2b]"),
+            Ok(NbtTag::IntArray(vec![1, 2]))
+        );
+        assert_eq!(
+            NbtTag::from_snbt("[1ef, 300b, 1e5]"),
+            Ok(NbtTag::List(vec![
+                NbtTag::String("1ef".to_owned()),
+                NbtTag::String("300b".to_owned()),
+                NbtTag::Double(1e5),
+            ]))
+        );
+        assert_eq!(
+            Chat::from_snbt("{text:1ef}"),
+            Ok(Chat::Component(ChatComponent {
+                content: TextContent::new_literal("1ef"),
+                ..Default::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            NbtTag::from_snbt("{a:1,\n  b 2}"),
+            Err(SnbtError::UnexpectedChar {
+                expected: "':'",
+                found: '2',
+                line: 2,
+                column: 5
+            })
+        );
+        assert_eq!(
+            NbtTag::from_snbt("{a:'open"),
+            Err(SnbtError::UnexpectedEnd { line: 1, column: 9 })
+        );
+        assert_eq!(
+            NbtTag::from_snbt("[B; 300]"),
+            Err(SnbtError::InvalidArrayElement {
+                array_type: 'B',
+                line: 1,
+                column: 5
+            })
+        );
+        assert!(matches!(
+            NbtTag::from_snbt(&"[".repeat(200000)),
+            Err(SnbtError::TooDeep { max_depth: 512, .. })
+        ));
+        assert!(matches!(
+            Chat::from_snbt("{color:'red'}"),
+            Err(SnbtError::InvalidComponent { .. })
+        ));
+    }
+
+    #[test]
+    fn test_chat() {
+        let chat = Chat::from_snbt("{text:'hi',color:red,bold:true,extra:['!',{text:\"?\"}]}")
+            .unwrap()
+            .into_component();
+        assert_eq!(chat.content, TextContent::new_literal("hi"));
+        assert_eq!(chat.color, Some(ChatColor::Red));
+        assert_eq!(chat.bold, Some(true));
+
+        let snbt = chat.to_snbt().unwrap();
+        assert_eq!(snbt, r#"{bold:1b,color:"red",extra:["!","?"],text:"hi"}"#);
+        // Text only components get shortened to plain strings
+        let reparsed = ChatComponent::from_snbt(&snbt).unwrap();
+        assert_eq!(reparsed.extra[1], Chat::Text("?".to_owned()));
+        assert_eq!(reparsed.to_snbt().unwrap(), snbt);
+    }
+
+    #[test]
+    fn test_inline_events() {
+        let chat = Chat::from_snbt("{text:'a',click_event:{action:'open_url',url:'https://x'}}")
+            .unwrap()
+            .into_component();
+        let click_event = chat.click_event.as_ref().unwrap();
+        assert_eq!(click_event.action, ClickAction::OpenUrl);
+        assert_eq!(click_event.value, "https://x");
+        assert_eq!(
+            chat.to_snbt().unwrap(),
+            r#"{clickEvent:{action:"open_url",value:"https://x"},text:"a"}"#
+        );
+        assert_eq!(
+            chat.to_snbt_with(HoverFormat::Inline).unwrap(),
+            r#"{click_event:{action:"open_url",url:"https://x"},text:"a"}"#
+        );
+
+        let chat = Chat::from_snbt(
+            "{text:'b',hover_event:{action:'show_entity',id:'minecraft:pig',uuid:[I;0,0,0,1]}}",
+        )
+        .unwrap();
+        let snbt = chat.to_snbt_with(HoverFormat::Inline).unwrap();
+        assert_eq!(Chat::from_snbt(&snbt).unwrap(), chat);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{
+    chat::{Chat, ChatComponent, ClickEvent, TextContent},
+    formatting::{ChatColor, ChatFormat},
+    hover::HoverEvent,
+    legacy::{LegacyDialect, LegacyTokenKind},
+};
+
+/// Formats in the order their legacy codes get written
+pub(crate) const FORMATS: [ChatFormat; 5] = [
+    ChatFormat::Bold,
+    ChatFormat::Italic,
+    ChatFormat::Strikethrough,
+    ChatFormat::Underlined,
+    ChatFormat::Obfuscated,
+];
+
+/// The effective style of some text, after inheriting everything unset from its parents.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style {
+    /// None means the default color of wherever the text is shown (usually white)
+    pub color: Option<ChatColor>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underlined: bool,
+    pub strikethrough: bool,
+    pub obfuscated: bool,
+    pub font: Option<String>,
+    pub insertion: Option<String>,
+    pub click_event: Option<ClickEvent>,
+    pub hover_event: Option<HoverEvent>,
+}
+
+/// Text with the same effective style
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextRun {
+    pub text: String,
+    pub style: Style,
+}
+
+impl Style {
+    /// Style of a component with this being the style of its parent. Like vanilla,
+    /// everything the component doesn't set is inherited (also explicit false overrides).
+    pub fn inherit(&self, component: &ChatComponent) -> Self {
+        Self {
+            color: match component.color {
+                Some(ChatColor::Reset) => None,
+                Some(color) => Some(color),
+                None => self.color,
+            },
+            bold: component.bold.unwrap_or(self.bold),
+            italic: component.italic.unwrap_or(self.italic),
+            underlined: component.underlined.unwrap_or(self.underlined),
+            strikethrough: component.strikethrough.unwrap_or(self.strikethrough),
+            obfuscated: component.obfuscated.unwrap_or(self.obfuscated),
+            font: component.font.clone().or_else(|| self.font.clone()),
+            insertion: component
+                .insertion
+                .clone()
+                .or_else(|| self.insertion.clone()),
+            click_event: component
+                .click_event
+                .clone()
+                .or_else(|| self.click_event.clone()),
+            hover_event: component
+                .hover_event
+                .clone()
+                .or_else(|| self.hover_event.clone()),
+        }
+    }
+
+    pub fn has_format(&self, format: ChatFormat) -> bool {
+        match format {
+            ChatFormat::Bold => self.bold,
+            ChatFormat::Italic => self.italic,
+            ChatFormat::Underlined => self.underlined,
+            ChatFormat::Strikethrough => self.strikethrough,
+            ChatFormat::Obfuscated => self.obfuscated,
+        }
+    }
+
+    pub fn set_format(&mut self, format: ChatFormat, value: bool) {
+        match format {
+            ChatFormat::Bold => self.bold = value,
+            ChatFormat::Italic => self.italic = value,
+            ChatFormat::Underlined => self.underlined = value,
+            ChatFormat::Strikethrough => self.strikethrough = value,
+            ChatFormat::Obfuscated => self.obfuscated = value,
+        }
+    }
+
+    /// Apply a legacy code found inside of text, like the client does.
+    /// Colors clear all formats and reset goes back to the style of the containing component.
+    pub fn apply_legacy_code(&self, base: &Style, code: char) -> Option<Self> {
+        if let Ok(format) = ChatFormat::from_format_code_char(code) {
+            let mut style = self.clone();
+            style.set_format(format, true);
+            return Some(style);
+        }
+        match ChatColor::from_color_code_char(code).ok()? {
+            ChatColor::Reset => Some(base.clone()),
+            color => Some(self.with_legacy_color(color)),
+        }
+    }
+
+    /// Switch to a color like a legacy color code does, clearing all formats
+    pub fn with_legacy_color(&self, color: ChatColor) -> Self {
+        let mut style = self.clone();
+        style.color = Some(color);
+        for format in FORMATS {
+            style.set_format(format, false);
+        }
+        style
+    }
+}
+
+/// Append text to runs, merging it with the last run if the style is the same
+pub(crate) fn push_run(runs: &mut Vec<TextRun>, text: &str, style: &Style) {
+    if text.is_empty() {
+        return;
+    }
+    match runs.last_mut() {
+        Some(last) if &last.style == style => last.text.push_str(text),
+        _ => runs.push(TextRun {
+            text: text.to_owned(),
+            style: style.clone(),
+        }),
+    }
+}
+
+/// Flat components with the effective style of each run, for parsers of formats without nesting
+pub(crate) fn runs_to_chat(runs: &[TextRun]) -> Chat {
+    let mut components = runs
+        .iter()
+        .map(|run| ChatComponent {
+            content: TextContent::new_literal(&run.text),
+            color: run.style.color,
+            bold: run.style.bold.then_some(true),
+            italic: run.style.italic.then_some(true),
+            underlined: run.style.underlined.then_some(true),
+            strikethrough: run.style.strikethrough.then_some(true),
+            obfuscated: run.style.obfuscated.then_some(true),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    // Siblings inherit the style of their parent, so they can't be children of the first one
+    if components.len() == 1 {
+        return Chat::Component(components.remove(0));
+    }
+    Chat::Component(ChatComponent {
+        extra: components.into_iter().map(Chat::Component).collect(),
+        ..Default::default()
+    })
+}
+
+/// Split text containing legacy codes into runs, starting with the given style
+pub(crate) fn push_legacy_runs(runs: &mut Vec<TextRun>, legacy_text: &str, base: &Style) {
+    let dialect = LegacyDialect::section();
+    let mut style = base.clone();
+    let mut text = String::new();
+    for token in dialect.tokenize(legacy_text) {
+        let new_style = match token.kind {
+            LegacyTokenKind::Text => {
+                text.push_str(&legacy_text[token.span]);
+                continue;
+            }
+            LegacyTokenKind::Reset => base.clone(),
+            LegacyTokenKind::Color(color) => style.with_legacy_color(color),
+            LegacyTokenKind::Format(format) => {
+                let mut new_style = style.clone();
+                new_style.set_format(format, true);
+                new_style
+            }
+            // Unknown codes and a trailing § are dropped, like the client does
+            LegacyTokenKind::Escaped | LegacyTokenKind::Unknown(_) | LegacyTokenKind::Dangling => {
+                continue
+            }
+        };
+        push_run(runs, &text, &style);
+        text.clear();
+        style = new_style;
+    }
+    push_run(runs, &text, &style);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inherit() {
+        let parent = Style {
+            color: Some(ChatColor::Red),
+            bold: true,
+            ..Default::default()
+        };
+        let child = parent.inherit(&ChatComponent {
+            content: TextContent::new_literal("child"),
+            bold: Some(false),
+            italic: Some(true),
+            ..Default::default()
+        });
+        assert_eq!(
+            child,
+            Style {
+                color: Some(ChatColor::Red),
+                italic: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            child.inherit(&ChatComponent {
+                color: Some(ChatColor::Reset),
+                ..Default::default()
+            }),
+            Style {
+                italic: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_legacy_runs() {
+        let base = Style {
+            underlined: true,
+            ..Default::default()
+        };
+        let mut runs = Vec::new();
+        push_legacy_runs(&mut runs, "a§l§lb§9c§rd§", &base);
+        assert_eq!(
+            runs.iter()
+                .map(|run| (run.text.as_str(), run.style.color, run.style.bold))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a", None, false),
+                ("b", None, true),
+                ("c", Some(ChatColor::Blue), false),
+                ("d", None, false),
+            ]
+        );
+        assert!(!runs[2].style.underlined);
+        assert!(runs[3].style.underlined);
+    }
+}
@@ -0,0 +1,529 @@
+use serde_json::Value;
+
+use crate::{
+    chat::{Chat, ChatComponent, TextContent, TranslatablePlaceholder},
+    formatting::ChatColor,
+    hover::HoverItem,
+    nbt::NbtTag,
+    translator::TranslationProvider,
+};
+
+/// How many container entries get listed before "and N more..."
+const CONTAINER_ENTRIES: usize = 5;
+
+/// HideFlags bits (before 1.20.5)
+const HIDE_ENCHANTMENTS: i64 = 1;
+const HIDE_ADDITIONAL: i64 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+}
+
+impl Rarity {
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "common" => Rarity::Common,
+            "uncommon" => Rarity::Uncommon,
+            "rare" => Rarity::Rare,
+            "epic" => Rarity::Epic,
+            _ => return None,
+        })
+    }
+
+    /// Rarity of items without an explicit one. Only covers vanilla items that aren't common.
+    pub fn of_item(id: &str) -> Self {
+        match id.strip_prefix("minecraft:").unwrap_or(id) {
+            "command_block"
+            | "chain_command_block"
+            | "repeating_command_block"
+            | "command_block_minecart"
+            | "structure_block"
+            | "structure_void"
+            | "jigsaw"
+            | "barrier"
+            | "light"
+            | "debug_stick"
+            | "dragon_egg"
+            | "enchanted_golden_apple"
+            | "mojang_banner_pattern" => Rarity::Epic,
+            "golden_apple" | "beacon" | "conduit" | "end_crystal" => Rarity::Rare,
+            id if id.starts_with("music_disc_") => Rarity::Rare,
+            "enchanted_book"
+            | "experience_bottle"
+            | "dragon_breath"
+            | "elytra"
+            | "nether_star"
+            | "totem_of_undying"
+            | "heart_of_the_sea"
+            | "creeper_head"
+            | "dragon_head"
+            | "piglin_head"
+            | "player_head"
+            | "skeleton_skull"
+            | "wither_skeleton_skull"
+            | "zombie_head"
+            | "creeper_banner_pattern"
+            | "skull_banner_pattern"
+            | "piglin_banner_pattern" => Rarity::Uncommon,
+            _ => Rarity::Common,
+        }
+    }
+
+    /// Enchanted items are shown one rarity higher (at least rare)
+    pub fn enchanted(self) -> Self {
+        match self {
+            Rarity::Common | Rarity::Uncommon => Rarity::Rare,
+            Rarity::Rare | Rarity::Epic => Rarity::Epic,
+        }
+    }
+
+    pub fn color(self) -> ChatColor {
+        match self {
+            Rarity::Common => ChatColor::White,
+            Rarity::Uncommon => ChatColor::Yellow,
+            Rarity::Rare => ChatColor::Aqua,
+            Rarity::Epic => ChatColor::LightPurple,
+        }
+    }
+}
+
+/// Roman numeral for enchantment levels without a translation
+pub fn roman_numeral(mut number: i64) -> String {
+    if number <= 0 {
+        return number.to_string();
+    }
+    const NUMERALS: [(i64, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut output = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            output.push_str(numeral);
+            number -= value;
+        }
+    }
+    output
+}
+
+/// Enchantments that only have one level and therefore don't show it
+fn is_single_level(id: &str) -> bool {
+    matches!(
+        id.strip_prefix("minecraft:").unwrap_or(id),
+        "aqua_affinity"
+            | "binding_curse"
+            | "channeling"
+            | "flame"
+            | "infinity"
+            | "mending"
+            | "multishot"
+            | "silk_touch"
+            | "vanishing_curse"
+    )
+}
+
+/// Split a resource location into namespace and path
+fn split_id(id: &str) -> (&str, &str) {
+    id.split_once(':').unwrap_or(("minecraft", id))
+}
+
+fn translatable(translate: String, with: Vec<TranslatablePlaceholder>) -> ChatComponent {
+    ChatComponent {
+        content: TextContent::Translatable {
+            translate,
+            with: (!with.is_empty()).then_some(with),
+            fallback: None,
+        },
+        ..Default::default()
+    }
+}
+
+/// Read a text component, which is either json or a json string (possibly with json inside)
+fn text_value(value: &Value) -> Option<Chat> {
+    match value {
+        Value::String(text) => Some(
+            serde_json::from_str(text)
+                .ok()
+                .filter(|_| text.starts_with(['{', '[', '"']))
+                .unwrap_or_else(|| Chat::Legacy(text.to_owned())),
+        ),
+        _ => serde_json::from_value(value.clone()).ok(),
+    }
+}
+
+fn text_list(value: Option<&Value>) -> Vec<Chat> {
+    value
+        .and_then(Value::as_array)
+        .map(|lines| lines.iter().filter_map(text_value).collect())
+        .unwrap_or_default()
+}
+
+/// The tooltip relevant parts of an item, from either data components or the old nbt tag
+#[derive(Debug, Default)]
+struct ItemData {
+    id: String,
+    count: i64,
+    custom_name: Option<Chat>,
+    item_name: Option<Chat>,
+    rarity: Option<Rarity>,
+    lore: Vec<Chat>,
+    enchantments: Vec<(String, i64)>,
+    stored_enchantments: Vec<(String, i64)>,
+    show_enchantments: bool,
+    contents: Vec<ItemData>,
+    show_contents: bool,
+}
+
+fn enchantment_list(value: Option<&Value>) -> Vec<(String, i64)> {
+    let Some(value) = value else {
+        return Vec::new();
+    };
+    match value {
+        // Before 1.20.5: [{id: "minecraft:sharpness", lvl: 5s}]
+        Value::Array(entries) => entries
+            .iter()
+            .filter_map(|entry| {
+                Some((
+                    entry.get("id")?.as_str()?.to_owned(),
+                    entry.get("lvl")?.as_i64()?,
+                ))
+            })
+            .collect(),
+        // 1.20.5: {levels: {...}, show_in_tooltip: false}, 1.21.5: {"minecraft:sharpness": 5}
+        Value::Object(entries) => entries
+            .get("levels")
+            .and_then(Value::as_object)
+            .unwrap_or(entries)
+            .iter()
+            .filter_map(|(id, level)| Some((id.to_owned(), level.as_i64()?)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl ItemData {
+    fn from_tag(id: &str, count: i64, tag: Option<&Value>) -> Self {
+        let display = tag.and_then(|tag| tag.get("display"));
+        let hide_flags = tag
+            .and_then(|tag| tag.get("HideFlags"))
+            .and_then(Value::as_i64)
+            .unwrap_or(0);
+        let contents = tag
+            .and_then(|tag| tag.get("BlockEntityTag")?.get("Items")?.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| {
+                        Some(ItemData::from_tag(
+                            item.get("id")?.as_str()?,
+                            item.get("Count").and_then(Value::as_i64).unwrap_or(1),
+                            item.get("tag"),
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        ItemData {
+            id: id.to_owned(),
+            count,
+            custom_name: display.and_then(|display| text_value(display.get("Name")?)),
+            lore: text_list(display.and_then(|display| display.get("Lore"))),
+            enchantments: enchantment_list(tag.and_then(|tag| tag.get("Enchantments"))),
+            stored_enchantments: enchantment_list(
+                tag.and_then(|tag| tag.get("StoredEnchantments")),
+            ),
+            show_enchantments: hide_flags & HIDE_ENCHANTMENTS == 0,
+            contents,
+            show_contents: hide_flags & HIDE_ADDITIONAL == 0,
+            ..Default::default()
+        }
+    }
+
+    fn from_components(id: &str, count: i64, components: &Value) -> Self {
+        let get = |name: &str| {
+            components
+                .get(format!("minecraft:{name}"))
+                .or_else(|| components.get(name))
+        };
+        let hidden = |name: &str| {
+            let hidden_components = get("tooltip_display")
+                .and_then(|display| display.get("hidden_components"))
+                .and_then(Value::as_array);
+            hidden_components.is_some_and(|hidden| {
+                hidden
+                    .iter()
+                    .any(|hidden| hidden.as_str().map(|hidden| split_id(hidden).1) == Some(name))
+            })
+        };
+        let shown = |name: &str| {
+            !hidden(name)
+                && get(name)
+                    .and_then(|value| value.get("show_in_tooltip"))
+                    .and_then(Value::as_bool)
+                    .unwrap_or(true)
+        };
+        let contents = get("container")
+            .and_then(Value::as_array)
+            .map(|slots| {
+                slots
+                    .iter()
+                    .filter_map(|slot| ItemData::from_value(slot.get("item")?))
+                    .collect()
+            })
+            .unwrap_or_default();
+        ItemData {
+            id: id.to_owned(),
+            count,
+            custom_name: get("custom_name").and_then(text_value),
+            item_name: get("item_name").and_then(text_value),
+            rarity: get("rarity")
+                .and_then(Value::as_str)
+                .and_then(Rarity::parse),
+            lore: text_list(get("lore")),
+            enchantments: enchantment_list(get("enchantments")),
+            stored_enchantments: enchantment_list(get("stored_enchantments")),
+            show_enchantments: shown("enchantments") && shown("stored_enchantments"),
+            contents,
+            show_contents: !hidden("container") && get("hide_additional_tooltip").is_none(),
+        }
+    }
+
+    /// Read an item stack in the 1.20.5+ format ({id, count, components})
+    fn from_value(item: &Value) -> Option<Self> {
+        let id = item.get("id")?.as_str()?;
+        let count = item.get("count").and_then(Value::as_i64).unwrap_or(1);
+        Some(match item.get("components") {
+            Some(components) => ItemData::from_components(id, count, components),
+            None => ItemData::from_tag(id, count, None),
+        })
+    }
+
+    fn from_hover_item(item: &HoverItem) -> Self {
+        match (&item.components, &item.tag) {
+            (Some(components), _) => {
+                ItemData::from_components(&item.id, item.count.into(), components)
+            }
+            (None, tag) => {
+                let tag = tag
+                    .as_deref()
+                    .and_then(|tag| NbtTag::from_snbt(tag).ok())
+                    .and_then(|tag| tag.to_json_value().ok());
+                ItemData::from_tag(&item.id, item.count.into(), tag.as_ref())
+            }
+        }
+    }
+
+    /// The name without rarity styling, like vanilla's hover name
+    fn hover_name(&self, translator: &dyn TranslationProvider) -> Chat {
+        if let Some(name) = self.custom_name.as_ref().or(self.item_name.as_ref()) {
+            return name.clone();
+        }
+        let (namespace, path) = split_id(&self.id);
+        let item_key = format!("item.{namespace}.{path}");
+        let block_key = format!("block.{namespace}.{path}");
+        let key = if !translator.contains(&item_key) && translator.contains(&block_key) {
+            block_key
+        } else {
+            item_key
+        };
+        Chat::Component(translatable(key, Vec::new()))
+    }
+
+    fn rarity(&self) -> Rarity {
+        let rarity = self.rarity.unwrap_or_else(|| Rarity::of_item(&self.id));
+        if self.enchantments.is_empty() {
+            rarity
+        } else {
+            rarity.enchanted()
+        }
+    }
+
+    fn tooltip(&self, translator: &dyn TranslationProvider) -> Vec<Chat> {
+        let mut lines = Vec::new();
+        lines.push(Chat::Component(ChatComponent {
+            color: Some(self.rarity().color()),
+            italic: self.custom_name.is_some().then_some(true),
+            extra: vec![self.hover_name(translator)],
+            ..Default::default()
+        }));
+
+        if self.show_contents {
+            let stacks: Vec<_> = self.contents.iter().filter(|item| item.count > 0).collect();
+            for item in stacks.iter().take(CONTAINER_ENTRIES) {
+                lines.push(Chat::Component(translatable(
+                    "container.shulkerBox.itemCount".to_owned(),
+                    vec![
+                        TranslatablePlaceholder::Chat(item.hover_name(translator)),
+                        TranslatablePlaceholder::Number(item.count),
+                    ],
+                )));
+            }
+            if stacks.len() > CONTAINER_ENTRIES {
+                let mut more = translatable(
+                    "container.shulkerBox.more".to_owned(),
+                    vec![TranslatablePlaceholder::Number(
+                        (stacks.len() - CONTAINER_ENTRIES) as i64,
+                    )],
+                );
+                more.italic = Some(true);
+                lines.push(Chat::Component(more));
+            }
+        }
+
+        if self.show_enchantments {
+            for (id, level) in self.stored_enchantments.iter().chain(&self.enchantments) {
+                let (namespace, path) = split_id(id);
+                let mut line = translatable(format!("enchantment.{namespace}.{path}"), Vec::new());
+                line.color = Some(if path.ends_with("_curse") {
+                    ChatColor::Red
+                } else {
+                    ChatColor::Gray
+                });
+                if *level != 1 || !is_single_level(id) {
+                    let mut level_component =
+                        translatable(format!("enchantment.level.{level}"), Vec::new());
+                    if let TextContent::Translatable { fallback, .. } = &mut level_component.content
+                    {
+                        *fallback = Some(roman_numeral(*level));
+                    }
+                    line.extra = vec![
+                        Chat::Component(ChatComponent {
+                            content: TextContent::new_literal(" "),
+                            ..Default::default()
+                        }),
+                        Chat::Component(level_component),
+                    ];
+                }
+                lines.push(Chat::Component(line));
+            }
+        }
+
+        for lore in &self.lore {
+            lines.push(Chat::Component(ChatComponent {
+                color: Some(ChatColor::DarkPurple),
+                italic: Some(true),
+                extra: vec![lore.clone()],
+                ..Default::default()
+            }));
+        }
+        lines
+    }
+}
+
+impl HoverItem {
+    /// The lines vanilla shows when hovering the item: its name in the rarity color,
+    /// container contents, enchantments and lore
+    pub fn tooltip(&self, translator: &dyn TranslationProvider) -> Vec<Chat> {
+        ItemData::from_hover_item(self).tooltip(translator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{chat::TextFormatter, translator::Translator};
+
+    fn render(lines: &[Chat], translator: &Translator) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.to_legacy_string(translator))
+            .collect()
+    }
+
+    #[test]
+    fn test_tag_tooltip() {
+        let translator = Translator::from_translation_content(
+            r#"{"block.minecraft.shulker_box": "Shulker Box", "item.minecraft.diamond_sword": "Diamond Sword", "block.minecraft.stone": "Stone",
+                "container.shulkerBox.itemCount": "%s x%s", "container.shulkerBox.more": "and %s more...",
+                "enchantment.minecraft.sharpness": "Sharpness", "enchantment.minecraft.mending": "Mending",
+                "enchantment.minecraft.vanishing_curse": "Curse of Vanishing", "enchantment.level.5": "V"}"#,
+        )
+        .unwrap();
+        let sword = HoverItem {
+            id: "minecraft:diamond_sword".to_owned(),
+            count: 1,
+            components: None,
+            tag: Some(r#"{Enchantments:[{id:"minecraft:sharpness",lvl:5s},{id:"minecraft:mending",lvl:1s},{id:"minecraft:vanishing_curse",lvl:1s},{id:"minecraft:sharpness",lvl:12s}],display:{Lore:['{"text":"Sharp"}','{"text":"Red","color":"red"}']}}"#.to_owned()),
+        };
+        assert_eq!(
+            render(&sword.tooltip(&translator), &translator),
+            [
+                "§bDiamond Sword",
+                "§7Sharpness V",
+                "§7Mending",
+                "§cCurse of Vanishing",
+                "§7Sharpness XII",
+                "§5§oSharp",
+                "§c§oRed",
+            ]
+        );
+
+        let items = (0..7)
+            .map(|slot| {
+                format!(
+                    "{{Slot:{slot}b,id:\"minecraft:stone\",Count:{}b}}",
+                    slot + 1
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let shulker_box = HoverItem {
+            id: "minecraft:shulker_box".to_owned(),
+            count: 1,
+            components: None,
+            tag: Some(format!(
+                "{{BlockEntityTag:{{Items:[{items}]}},display:{{Name:'\"Stuff\"'}}}}"
+            )),
+        };
+        assert_eq!(
+            render(&shulker_box.tooltip(&translator), &translator),
+            [
+                "§f§oStuff",
+                "Stone x1",
+                "Stone x2",
+                "Stone x3",
+                "Stone x4",
+                "Stone x5",
+                "§oand 2 more...",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_component_tooltip() {
+        let translator = Translator::default();
+        let item = HoverItem {
+            id: "minecraft:golden_apple".to_owned(),
+            count: 3,
+            components: Some(json!({
+                "minecraft:rarity": "epic",
+                "minecraft:item_name": {"text": "Apple"},
+                "minecraft:lore": ["plain"],
+                "minecraft:enchantments": {"levels": {"minecraft:unbreaking": 3}, "show_in_tooltip": false},
+            })),
+            tag: None,
+        };
+        assert_eq!(
+            render(&item.tooltip(&translator), &translator),
+            ["§dApple", "§5§oplain"]
+        );
+        assert_eq!(roman_numeral(3999), "MMMCMXCIX");
+    }
+}
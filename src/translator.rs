@@ -1,10 +1,259 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
 
 use once_cell::sync::Lazy;
-use regex::{Captures, Regex};
+use regex::Regex;
+
+use crate::{
+    chat::{Chat, ChatComponent, TranslatablePlaceholder},
+    error::TranslationError,
+};
 
 static ARG_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("%(?:(\\d+)\\$)?([A-Za-z%]|$)").unwrap());
+/// Numeric specifiers, which .lang files may contain and vanilla turned into %s
+static NUMERIC_ARG_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("%(\\d+\\$)?[\\d.]*[df]").unwrap());
+
+/// Game version the embedded en_us translations are taken from
+#[cfg(feature = "vanilla-en-us")]
+pub const VANILLA_VERSION: &str = "1.21.1";
+
+/// The complete en_us.json of [`VANILLA_VERSION`], extracted from its client jar with
+/// `cargo run --example extract_en_us -- client.jar assets/en_us.json`
+#[cfg(feature = "vanilla-en-us")]
+static VANILLA_EN_US: Lazy<Translator> = Lazy::new(|| {
+    Translator::from_translation_content(include_str!("../assets/en_us.json"))
+        .expect("Embedded en_us.json is valid")
+});
+
+#[cfg(feature = "vanilla-en-us")]
+static VANILLA_EN_US_KEYS: Lazy<BTreeMap<&'static str, usize>> =
+    Lazy::new(|| VANILLA_EN_US.argument_counts());
+
+/// Keys of the embedded en_us translations with how many arguments they take
+#[cfg(feature = "vanilla-en-us")]
+pub fn vanilla_en_us_keys() -> &'static BTreeMap<&'static str, usize> {
+    &VANILLA_EN_US_KEYS
+}
+
+/// What to do with translations that don't fit their arguments
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TranslationMode {
+    /// Like vanilla: invalid formats and missing arguments show the raw translation,
+    /// unused arguments are ignored
+    #[default]
+    Lenient,
+    /// Report every mismatch as an error
+    Strict,
+}
+
+enum Segment<'a> {
+    Literal(&'a str),
+    Arg(usize),
+}
+
+struct Decomposed<'a> {
+    segments: Vec<Segment<'a>>,
+    /// Position of the first argument the format never references
+    unused: Option<usize>,
+}
+
+/// Split a format string the way vanilla's TranslatableContents does (only %s, %n$s and %%)
+fn decompose<'a>(format: &'a str, arg_count: usize) -> Result<Decomposed<'a>, TranslationError> {
+    let mut segments = Vec::new();
+    let mut used = vec![false; arg_count];
+    let mut last_end = 0;
+    let mut next_position = 0;
+    let push_literal = |segments: &mut Vec<Segment<'a>>, start: usize, end: usize| {
+        let literal = &format[start..end];
+        if let Some(stray) = literal.find('%') {
+            return Err(TranslationError::InvalidFormat {
+                offset: start + stray,
+            });
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(())
+    };
+
+    for cap in ARG_REGEX.captures_iter(format) {
+        let entire_match = cap.get(0).unwrap();
+        let offset = entire_match.start();
+        push_literal(&mut segments, last_end, offset)?;
+        last_end = entire_match.end();
+
+        if entire_match.as_str() == "%%" {
+            segments.push(Segment::Literal("%"));
+            continue;
+        } else if cap.get(2).map(|m| m.as_str()) != Some("s") {
+            return Err(TranslationError::UnsupportedSpecifier {
+                specifier: entire_match.as_str().to_owned(),
+                offset,
+            });
+        }
+        let position = match cap.get(1) {
+            // Too large to be an index anyway
+            Some(position) => position.as_str().parse().unwrap_or(usize::MAX),
+            None => {
+                next_position += 1;
+                next_position
+            }
+        };
+        let index = position
+            .checked_sub(1)
+            .filter(|index| *index < arg_count)
+            .ok_or(TranslationError::MissingArgument { position, offset })?;
+        used[index] = true;
+        segments.push(Segment::Arg(index));
+    }
+    push_literal(&mut segments, last_end, format.len())?;
+
+    Ok(Decomposed {
+        segments,
+        unused: used.iter().position(|used| !used).map(|index| index + 1),
+    })
+}
+
+/// Decompose in the given mode. Lenient mode falls back to the raw format as a single literal.
+fn decompose_with(
+    format: &str,
+    arg_count: usize,
+    mode: TranslationMode,
+) -> Result<Vec<Segment<'_>>, TranslationError> {
+    match (decompose(format, arg_count), mode) {
+        (
+            Ok(Decomposed {
+                unused: Some(position),
+                ..
+            }),
+            TranslationMode::Strict,
+        ) => Err(TranslationError::UnusedArgument { position }),
+        (Ok(decomposed), _) => Ok(decomposed.segments),
+        (Err(error), TranslationMode::Strict) => Err(error),
+        (Err(_), TranslationMode::Lenient) => Ok(vec![Segment::Literal(format)]),
+    }
+}
+
+/// How many arguments a format string takes. Invalid formats take none, since they are shown raw.
+pub fn argument_count(format: &str) -> usize {
+    let mut next_position = 0;
+    let highest_position = ARG_REGEX
+        .captures_iter(format)
+        .filter(|cap| cap.get(2).is_some_and(|m| m.as_str() == "s"))
+        .map(|cap| match cap.get(1) {
+            Some(position) => position.as_str().parse().unwrap_or(0),
+            None => {
+                next_position += 1;
+                next_position
+            }
+        })
+        .max()
+        .unwrap_or(0);
+    match decompose(format, highest_position) {
+        Ok(_) => highest_position,
+        Err(_) => 0,
+    }
+}
+
+/// Replace `\uXXXX` escapes (including surrogate pairs) with their characters
+fn unescape_unicode(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut units = Vec::new();
+    let mut rest = text;
+    loop {
+        let escape = rest
+            .strip_prefix("\\u")
+            .and_then(|hex| hex.get(..4))
+            .and_then(|hex| u16::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(unit) => {
+                units.push(unit);
+                rest = &rest[6..];
+                continue;
+            }
+            None => {
+                output.extend(
+                    char::decode_utf16(units.drain(..))
+                        .map(|chr| chr.unwrap_or(char::REPLACEMENT_CHARACTER)),
+                );
+            }
+        }
+        let Some(chr) = rest.chars().next() else {
+            break;
+        };
+        output.push(chr);
+        rest = &rest[chr.len_utf8()..];
+    }
+    output
+}
+
+/// Source of translations for rendering, like a [`Translator`], a database or several layers
+pub trait TranslationProvider {
+    /// The format string for a key, in the given locale or else the provider's default
+    fn lookup(&self, translate: &str, locale: Option<&str>) -> Option<Cow<'_, str>>;
+
+    /// Whether there is a translation for the key
+    fn contains(&self, translate: &str) -> bool {
+        self.lookup(translate, None).is_some()
+    }
+
+    /// Translate into a string with already rendered arguments
+    fn translate(&self, translate: &str, args: &[&str], fallback: Option<&str>) -> String {
+        self.translate_with(translate, args, fallback, TranslationMode::Lenient)
+            .unwrap_or_default()
+    }
 
+    fn translate_with(
+        &self,
+        translate: &str,
+        args: &[&str],
+        fallback: Option<&str>,
+        mode: TranslationMode,
+    ) -> Result<String, TranslationError> {
+        let format = self.lookup(translate, None);
+        let format = format.as_deref().unwrap_or(fallback.unwrap_or(translate));
+        Ok(decompose_with(format, args.len(), mode)?
+            .into_iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => literal,
+                Segment::Arg(index) => args[index],
+            })
+            .collect())
+    }
+
+    /// Translate into a chat tree, keeping the arguments as full components
+    fn translate_chat(
+        &self,
+        translate: &str,
+        args: &[TranslatablePlaceholder],
+        fallback: Option<&str>,
+    ) -> Chat {
+        let format = self.lookup(translate, None);
+        Translator::format_chat(
+            format.as_deref().unwrap_or(fallback.unwrap_or(translate)),
+            args,
+        )
+    }
+
+    fn translate_chat_with(
+        &self,
+        translate: &str,
+        args: &[TranslatablePlaceholder],
+        fallback: Option<&str>,
+        mode: TranslationMode,
+    ) -> Result<Chat, TranslationError> {
+        let format = self.lookup(translate, None);
+        Translator::format_chat_with(
+            format.as_deref().unwrap_or(fallback.unwrap_or(translate)),
+            args,
+            mode,
+        )
+    }
+}
+
+/// Translations of one locale, which ignores the locale of lookups
 #[derive(Clone, Debug, Default)]
 pub struct Translator {
     translations: HashMap<String, String>,
@@ -18,36 +267,301 @@ impl Translator {
         Ok(Self { translations })
     }
 
-    pub fn translate(&self, translate: &str, args: &[&str], fallback: Option<&str>) -> String {
-        let translate = translate.to_owned();
-        let translate = self
-            .translations
-            .get(&translate)
-            .map(|s| s.as_str())
-            .unwrap_or(&fallback.unwrap_or_else(|| translate.as_str()));
-
-        let mut i = 0;
-        ARG_REGEX
-            .replace_all(translate, |cap: &Captures| {
-                //let entire_match = cap.get(0).map(|m| m.as_str()).unwrap_or("");
-                let group_1 = cap.get(1).map(|m| m.as_str()).unwrap_or("");
-                let group_2 = cap.get(2).map(|m| m.as_str()).unwrap_or("");
-
-                if group_2 == "%" {
-                    "%%" // Literal percent sign
-                } else if group_2 != "s" {
-                    "" // Should always be %s
-                } else {
-                    let index = if let Ok(custom_arg_index) = group_1.parse::<usize>() {
-                        custom_arg_index - 1
-                    } else {
-                        let index = i;
-                        i += 1;
-                        index
-                    };
-                    args.get(index).unwrap_or(&"")
+    /// The embedded vanilla en_us translations of [`VANILLA_VERSION`]
+    #[cfg(feature = "vanilla-en-us")]
+    pub fn vanilla_en_us() -> Self {
+        VANILLA_EN_US.clone()
+    }
+
+    /// Read a pre-1.13 .lang file of key=value lines. Values may contain `\uXXXX` escapes.
+    pub fn from_lang_content(lang_file_content: &str) -> Self {
+        let translations = lang_file_content
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| {
+                let value = unescape_unicode(value);
+                let value = NUMERIC_ARG_REGEX.replace_all(&value, "%${1}s");
+                (key.to_owned(), value.into_owned())
+            })
+            .collect();
+        Self { translations }
+    }
+
+    /// The translation for the key
+    pub fn get(&self, translate: &str) -> Option<&str> {
+        self.translations.get(translate).map(|s| s.as_str())
+    }
+
+    /// Add the translations of other for keys without one
+    pub fn fill_from(&mut self, other: &Translator) {
+        for (key, value) in &other.translations {
+            if !self.translations.contains_key(key) {
+                self.translations.insert(key.to_owned(), value.to_owned());
+            }
+        }
+    }
+
+    /// Add the translations of other, replacing existing ones
+    pub fn overlay(&mut self, other: &Translator) {
+        self.translations.extend(
+            other
+                .translations
+                .iter()
+                .map(|(key, value)| (key.to_owned(), value.to_owned())),
+        );
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.translations.keys().map(|key| key.as_str())
+    }
+
+    /// Every key with how many arguments its translation takes
+    pub fn argument_counts(&self) -> BTreeMap<&str, usize> {
+        self.translations
+            .iter()
+            .map(|(key, format)| (key.as_str(), argument_count(format)))
+            .collect()
+    }
+
+    /// Split a format string into literal segments and arguments, the way vanilla decomposes
+    /// translatable contents. Literal segments inherit the style of the parent, arguments keep
+    /// their own components.
+    pub fn format_chat(format: &str, args: &[TranslatablePlaceholder]) -> Chat {
+        Self::format_chat_with(format, args, TranslationMode::Lenient)
+            .unwrap_or_else(|_| Chat::Text(format.to_owned()))
+    }
+
+    pub fn format_chat_with(
+        format: &str,
+        args: &[TranslatablePlaceholder],
+        mode: TranslationMode,
+    ) -> Result<Chat, TranslationError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        for segment in decompose_with(format, args.len(), mode)? {
+            match segment {
+                // Join "%%" with its surroundings
+                Segment::Literal(text) => literal.push_str(text),
+                Segment::Arg(index) => {
+                    if !literal.is_empty() {
+                        parts.push(Chat::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(match &args[index] {
+                        TranslatablePlaceholder::Chat(chat) => chat.clone(),
+                        TranslatablePlaceholder::Number(number) => Chat::Text(number.to_string()),
+                    });
                 }
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Chat::Text(literal));
+        }
+        Ok(Chat::Component(ChatComponent {
+            extra: parts,
+            ..Default::default()
+        }))
+    }
+}
+
+impl TranslationProvider for Translator {
+    fn lookup(&self, translate: &str, _locale: Option<&str>) -> Option<Cow<'_, str>> {
+        self.get(translate).map(Cow::Borrowed)
+    }
+}
+
+impl TranslationProvider for HashMap<String, String> {
+    fn lookup(&self, translate: &str, _locale: Option<&str>) -> Option<Cow<'_, str>> {
+        self.get(translate)
+            .map(|format| Cow::Borrowed(format.as_str()))
+    }
+}
+
+impl<T: TranslationProvider + ?Sized> TranslationProvider for &T {
+    fn lookup(&self, translate: &str, locale: Option<&str>) -> Option<Cow<'_, str>> {
+        (**self).lookup(translate, locale)
+    }
+}
+
+/// Overrides on top of a base, like server specific keys over the vanilla translations.
+/// Layers can be nested.
+#[derive(Clone, Debug, Default)]
+pub struct Layered<O, B> {
+    pub overrides: O,
+    pub base: B,
+}
+
+impl<O, B> Layered<O, B> {
+    pub fn new(overrides: O, base: B) -> Self {
+        Self { overrides, base }
+    }
+}
+
+impl<O: TranslationProvider, B: TranslationProvider> TranslationProvider for Layered<O, B> {
+    fn lookup(&self, translate: &str, locale: Option<&str>) -> Option<Cow<'_, str>> {
+        self.overrides
+            .lookup(translate, locale)
+            .or_else(|| self.base.lookup(translate, locale))
+    }
+}
+
+/// A provider with a fixed locale for lookups without one, to render for a specific player
+#[derive(Clone, Debug)]
+pub struct Localized<P> {
+    pub provider: P,
+    pub locale: String,
+}
+
+impl<P> Localized<P> {
+    pub fn new(provider: P, locale: &str) -> Self {
+        Self {
+            provider,
+            locale: locale.to_owned(),
+        }
+    }
+}
+
+impl<P: TranslationProvider> TranslationProvider for Localized<P> {
+    fn lookup(&self, translate: &str, locale: Option<&str>) -> Option<Cow<'_, str>> {
+        self.provider
+            .lookup(translate, Some(locale.unwrap_or(&self.locale)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chat::TextFormatter, formatting::ChatColor};
+
+    #[test]
+    fn test_translate_chat() {
+        let translator =
+            Translator::from_translation_content(r#"{"chat.type.text": "<%s> %s (100%%)"}"#)
+                .unwrap();
+        let name: Chat = serde_json::from_value(serde_json::json!({
+            "text": "Steve",
+            "color": "#FFAA01",
+            "clickEvent": { "action": "suggest_command", "value": "/msg Steve " }
+        }))
+        .unwrap();
+        let chat = translator.translate_chat(
+            "chat.type.text",
+            &[
+                TranslatablePlaceholder::Chat(name),
+                TranslatablePlaceholder::Number(7),
+            ],
+            None,
+        );
+        let runs = chat.to_text_runs(&translator);
+        let texts: Vec<_> = runs.iter().map(|run| run.text.as_str()).collect();
+        assert_eq!(texts, ["<", "Steve", "> 7 (100%)"]);
+        assert_eq!(
+            runs[1].style.color,
+            Some(ChatColor::Hex([0xFF, 0xAA, 0x01]))
+        );
+        assert!(runs[1].style.click_event.is_some());
+        assert_eq!(runs[2].style, runs[0].style);
+    }
+
+    #[test]
+    fn test_format_errors() {
+        let translator = Translator::default();
+        let strict = |format: &str, args: &[&str]| {
+            translator.translate_with(format, args, None, TranslationMode::Strict)
+        };
+        assert_eq!(strict("%2$s %1$s %%", &["a", "b"]), Ok("b a %".to_owned()));
+        assert_eq!(
+            strict("%0$s", &["a"]),
+            Err(TranslationError::MissingArgument {
+                position: 0,
+                offset: 0
+            })
+        );
+        assert_eq!(
+            strict("%s %s", &["a"]),
+            Err(TranslationError::MissingArgument {
+                position: 2,
+                offset: 3
             })
-            .to_string()
+        );
+        assert_eq!(
+            strict("%s", &["a", "b"]),
+            Err(TranslationError::UnusedArgument { position: 2 })
+        );
+        assert_eq!(
+            strict("%d%%", &["1"]),
+            Err(TranslationError::UnsupportedSpecifier {
+                specifier: "%d".to_owned(),
+                offset: 0
+            })
+        );
+        assert_eq!(
+            strict("100% sure", &[]),
+            Err(TranslationError::InvalidFormat { offset: 3 })
+        );
+
+        // Lenient falls back to the raw format, ignoring unused arguments
+        assert_eq!(
+            translator.translate("%0$s and %s", &["a"], None),
+            "%0$s and %s"
+        );
+        assert_eq!(translator.translate("%s", &["a", "b"], None), "a");
+        assert_eq!(translator.translate("50%", &[], None), "50%");
+    }
+
+    #[test]
+    fn test_argument_count() {
+        assert_eq!(argument_count("%s %s"), 2);
+        assert_eq!(argument_count("%3$s and %s"), 3);
+        assert_eq!(argument_count("100%% %s"), 1);
+        assert_eq!(argument_count("hi %"), 0);
+    }
+
+    #[cfg(feature = "vanilla-en-us")]
+    #[test]
+    fn test_vanilla_en_us() {
+        let translator = Translator::vanilla_en_us();
+        assert_eq!(
+            translator.translate("death.attack.player", &["Alex", "Steve"], None),
+            "Alex was slain by Steve"
+        );
+        let keys = vanilla_en_us_keys();
+        assert_eq!(keys["chat.type.text"], 2);
+        assert_eq!(keys["item.minecraft.diamond_sword"], 0);
+        assert_eq!(keys["death.attack.player.item"], 3);
+        assert_eq!(keys["translation.test.complex"], 3);
+        assert_eq!(keys["translation.test.invalid"], 0);
+    }
+
+    #[test]
+    fn test_providers() {
+        let base = Translator::from_translation_content(
+            r#"{"multiplayer.player.joined": "%s joined the game", "gui.yes": "Yes"}"#,
+        )
+        .unwrap();
+        let overrides =
+            HashMap::from([("multiplayer.player.joined".to_owned(), "+ %s".to_owned())]);
+        let layered = Layered::new(overrides, &base);
+        let chat = Chat::Component(ChatComponent {
+            content: crate::chat::TextContent::new_translatable(
+                "multiplayer.player.joined",
+                &["Steve"],
+            ),
+            ..Default::default()
+        });
+        assert_eq!(chat.to_plain_string(&layered), "+ Steve");
+        assert_eq!(chat.to_plain_string(&base), "Steve joined the game");
+        assert_eq!(layered.lookup("gui.yes", None).as_deref(), Some("Yes"));
+
+        let mut set = crate::locale::TranslatorSet::new();
+        set.insert("en_us", base.clone());
+        set.insert(
+            "de_de",
+            Translator::from_translation_content(r#"{"gui.yes": "Ja"}"#).unwrap(),
+        );
+        assert_eq!(set.lookup("gui.yes", None).as_deref(), Some("Yes"));
+        let german = Localized::new(&set, "de_at");
+        assert_eq!(german.translate("gui.yes", &[], None), "Ja");
+        assert_eq!(chat.to_plain_string(&german), "Steve joined the game");
     }
 }